@@ -1,52 +1,168 @@
 use rand::prelude::*;
 
-static GOOD_STATE_BER: f64 = 1e-6;
-static BAD_STATE_BER: f64 = 5e-3;
+use crate::common::ChannelParams;
 
-static GOOD_TO_BAD_TANSITION_P: f64 = 0.002;
-static BAD_TO_GOOD_TANSITION_P: f64 = 0.05;
+/// Row-sum tolerance for [`MarkovChannel::new_seeded`]'s transition-matrix
+/// validation; exact equality would reject sums that only differ by
+/// floating-point rounding.
+const TRANSITION_ROW_SUM_EPSILON: f64 = 1e-6;
 
-static GOOD_STATE: bool = true;
-static BAD_STATE: bool = false;
-
-/// Gilbert-Elliot model using Jump-Ahead logic.
+/// A per-frame corruption model consulted by [`crate::simulation::simulate_arq`].
 ///
-/// Calculates bit distances to state transitions to avoid bit-by-bit loops.
-pub struct GilbertElliotChannel {
-    state: bool,
-    bits_until_next_state_change: i64,
+/// Implemented by [`MarkovChannel`] (correlated, state-dependent bursts)
+/// and [`BernoulliChannel`] (independent per-frame corruption), so a
+/// sweep can contrast burst-loss recovery against the iid baseline it
+/// understates.
+pub trait ChannelModel {
+    /// Whether a frame of `num_bits` survives the channel.
+    fn frame_success(&mut self, num_bits: u64) -> bool;
 }
 
-impl Default for GilbertElliotChannel {
-    fn default() -> Self {
-        let mut channel = Self {
-            state: GOOD_STATE,
-            bits_until_next_state_change: 0,
-        };
+/// Selects which [`ChannelModel`] implementation to build, deferring
+/// construction until the run's seed and [`ChannelParams`] are known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelStrategy {
+    /// Correlated good/bad burst-loss model; see [`MarkovChannel::gilbert_elliott`].
+    GilbertElliot,
+    /// Independent, memoryless per-frame corruption; see [`BernoulliChannel`].
+    Bernoulli,
+}
 
-        channel.bits_until_next_state_change = channel.get_bits_to_transition();
-        channel
+impl ChannelStrategy {
+    /// Builds the selected channel model, seeded for reproducibility.
+    pub fn build(self, seed: u64, params: ChannelParams) -> Box<dyn ChannelModel + Send> {
+        match self {
+            Self::GilbertElliot => Box::new(MarkovChannel::gilbert_elliott(seed, params)),
+            Self::Bernoulli => Box::new(BernoulliChannel::new_seeded_with_params(seed, params)),
+        }
     }
 }
 
-impl GilbertElliotChannel {
-    /// Creates a new Gilbert-Elliot model channel.
-    pub fn new() -> Self {
-        Self::default()
+/// An N-state Markov channel model using jump-ahead logic.
+///
+/// Generalizes the two-state Gilbert-Elliot model (see
+/// [`Self::gilbert_elliott`]) to an arbitrary number of states, each with
+/// its own bit-error rate and its own per-bit transition probabilities
+/// to every other state. Calculates bit distances to the next state
+/// transition to avoid bit-by-bit loops.
+pub struct MarkovChannel {
+    ber: Vec<f64>,
+    transition: Vec<Vec<f64>>,
+    state: usize,
+    bits_until_next: i64,
+    rng: StdRng,
+}
+
+impl MarkovChannel {
+    /// Creates a new Markov channel with a fixed seed, starting in
+    /// `initial_state`, so the resulting state transitions and
+    /// corruption pattern are bit-for-bit reproducible across runs.
+    ///
+    /// `transition[i]` is state `i`'s row of per-bit transition
+    /// probabilities, including `transition[i][i]` (the probability of
+    /// staying in state `i` for another bit); `ber[i]` is state `i`'s
+    /// per-bit error rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ber` and `transition` don't have the same length, if
+    /// any row of `transition` isn't the same length as `ber`, or if any
+    /// row doesn't sum to 1.
+    pub fn new_seeded(
+        seed: u64,
+        ber: Vec<f64>,
+        transition: Vec<Vec<f64>>,
+        initial_state: usize,
+    ) -> Self {
+        assert_eq!(
+            ber.len(),
+            transition.len(),
+            "transition must have one row per state in ber"
+        );
+        for (i, row) in transition.iter().enumerate() {
+            assert_eq!(
+                row.len(),
+                ber.len(),
+                "transition row {i} must have one entry per state"
+            );
+            let sum: f64 = row.iter().sum();
+            assert!(
+                (sum - 1.0).abs() < TRANSITION_ROW_SUM_EPSILON,
+                "transition row {i} must sum to 1, got {sum}"
+            );
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let bits_until_next = Self::sample_dwell(initial_state, &transition, &mut rng);
+
+        Self {
+            ber,
+            transition,
+            state: initial_state,
+            bits_until_next,
+            rng,
+        }
     }
 
-    fn get_bits_to_transition(&self) -> i64 {
-        let p = if self.state == GOOD_STATE {
-            GOOD_TO_BAD_TANSITION_P
-        } else {
-            BAD_TO_GOOD_TANSITION_P
-        };
+    /// The two-state Gilbert-Elliot preset: a good state and a bad
+    /// state, with transition probabilities and per-bit error rates
+    /// taken from `params`. Reproduces the behavior of the original
+    /// fixed two-state model this type replaced.
+    pub fn gilbert_elliott(seed: u64, params: ChannelParams) -> Self {
+        Self::new_seeded(
+            seed,
+            vec![params.good_state_ber, params.bad_state_ber],
+            vec![
+                vec![1.0 - params.p_g_to_b, params.p_g_to_b],
+                vec![params.p_b_to_g, 1.0 - params.p_b_to_g],
+            ],
+            0,
+        )
+    }
+
+    /// Samples how many bits the channel dwells in `state` before its
+    /// next transition: `floor(ln(U) / ln(1 - p)) + 1`, where `p` is
+    /// `state`'s total per-bit exit probability (`1 - transition[state][state]`)
+    /// and `U` is uniform in `(0, 1)`. A state with `p == 0` never
+    /// leaves, so its dwell is clamped to `i64::MAX` rather than
+    /// dividing by `ln(1) == 0`.
+    fn sample_dwell(state: usize, transition: &[Vec<f64>], rng: &mut StdRng) -> i64 {
+        let p = 1.0 - transition[state][state];
+
+        if p <= 0.0 {
+            return i64::MAX;
+        }
 
-        let r: f64 = rand::rng().random();
+        let r: f64 = rng.random();
 
         (r.ln() / (1.0 - p).ln()).floor() as i64 + 1
     }
 
+    /// Picks the state to transition to once `state`'s dwell expires,
+    /// sampling the normalized off-diagonal row of `transition[state]`
+    /// (the self-transition weight is excluded, since dwell sampling
+    /// already accounts for it).
+    fn sample_next_state(state: usize, transition: &[Vec<f64>], rng: &mut StdRng) -> usize {
+        let row = &transition[state];
+        let exit_mass: f64 = row.iter().sum::<f64>() - row[state];
+
+        let mut r = rng.random::<f64>() * exit_mass;
+
+        for (j, &p) in row.iter().enumerate() {
+            if j == state {
+                continue;
+            }
+            if r < p {
+                return j;
+            }
+            r -= p;
+        }
+
+        // Floating-point rounding can leave a sliver of mass
+        // unassigned; fall back to the last off-diagonal state.
+        (0..row.len()).rev().find(|&j| j != state).unwrap_or(state)
+    }
+
     /// Wheter or not a frame with `num_bits` can successfully transmitted.
     pub fn frame_success(&mut self, num_bits: u64) -> bool {
         let mut bits_processed = 0;
@@ -55,35 +171,63 @@ impl GilbertElliotChannel {
         let num_bits = num_bits as i64;
 
         while bits_processed < num_bits {
-            let bits_in_chunk = (num_bits - bits_processed).min(self.bits_until_next_state_change);
-
-            let ber = if self.state == GOOD_STATE {
-                GOOD_STATE_BER
-            } else {
-                BAD_STATE_BER
-            };
+            let bits_in_chunk = (num_bits - bits_processed).min(self.bits_until_next);
 
             if !frame_corrupted {
-                let r: f64 = rand::rng().random();
+                let r: f64 = self.rng.random();
 
-                if r > (1.0 - ber).powf(bits_in_chunk as f64) {
+                if r > (1.0 - self.ber[self.state]).powf(bits_in_chunk as f64) {
                     frame_corrupted = true;
                 }
             }
 
             bits_processed += bits_in_chunk;
-            self.bits_until_next_state_change -= bits_in_chunk;
-
-            if self.bits_until_next_state_change <= 0 {
-                self.state = if self.state == GOOD_STATE {
-                    BAD_STATE
-                } else {
-                    GOOD_STATE
-                };
-                self.bits_until_next_state_change = self.get_bits_to_transition();
+            self.bits_until_next -= bits_in_chunk;
+
+            if self.bits_until_next <= 0 {
+                self.state = Self::sample_next_state(self.state, &self.transition, &mut self.rng);
+                self.bits_until_next = Self::sample_dwell(self.state, &self.transition, &mut self.rng);
             }
         }
 
         !frame_corrupted
     }
 }
+
+impl ChannelModel for MarkovChannel {
+    fn frame_success(&mut self, num_bits: u64) -> bool {
+        MarkovChannel::frame_success(self, num_bits)
+    }
+}
+
+/// Independent, memoryless per-frame corruption model.
+///
+/// Each frame is corrupted with a fixed probability derived from
+/// `params.good_state_ber`, unlike [`MarkovChannel`]'s correlated,
+/// state-dependent bursts. Useful as a baseline to quantify how much
+/// burst correlation matters to selective-repeat recovery: independent
+/// per-frame loss understates the back-to-back losses that stress it.
+pub struct BernoulliChannel {
+    rng: StdRng,
+    ber: f64,
+}
+
+impl BernoulliChannel {
+    /// Creates a new Bernoulli loss model with a fixed seed and explicit
+    /// channel-model parameters; the per-bit error rate is taken from
+    /// `params.good_state_ber`, since this model has no bad state.
+    pub fn new_seeded_with_params(seed: u64, params: ChannelParams) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ber: params.good_state_ber,
+        }
+    }
+}
+
+impl ChannelModel for BernoulliChannel {
+    fn frame_success(&mut self, num_bits: u64) -> bool {
+        let r: f64 = self.rng.random();
+
+        r <= (1.0 - self.ber).powf(num_bits as f64)
+    }
+}
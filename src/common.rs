@@ -24,3 +24,131 @@ pub static REVERSE_PATH: f64 = 0.010;
 
 /// Processing delay per frame
 pub static PROCESSING_DELAY: f64 = 0.002;
+
+/// When set, `SimplexChannel::send` falls back to the exact per-bit
+/// Gilbert-Elliot loop instead of the geometric run-length fast path.
+///
+/// The two samplers are only statistically equivalent in the limit of
+/// small per-bit probabilities; keep this flag around so the fast path
+/// can be validated against the exact loop it replaces.
+pub static USE_EXACT_BER_LOOP: bool = false;
+
+/// Gilbert-Elliot channel model parameters.
+///
+/// Groups the knobs that used to be baked in as the compile-time
+/// constants above (bit rate, per-state BER, state transition
+/// probabilities, per-frame processing delay) so a parameter sweep can
+/// override them at runtime instead of forcing a recompile per point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelParams {
+    /// Channel bit rate, in bits per second.
+    pub bit_rate: i64,
+    /// Bit error rate while the channel is in the good state.
+    pub good_state_ber: f64,
+    /// Bit error rate while the channel is in the bad state.
+    pub bad_state_ber: f64,
+    /// Good-to-bad state transition probability.
+    pub p_g_to_b: f64,
+    /// Bad-to-good state transition probability.
+    pub p_b_to_g: f64,
+    /// Per-frame processing delay.
+    pub processing_delay: f64,
+}
+
+impl Default for ChannelParams {
+    fn default() -> Self {
+        Self {
+            bit_rate: BIT_RATE,
+            good_state_ber: GOOD_STATE_BER,
+            bad_state_ber: BAD_STATE_BER,
+            p_g_to_b: P_G_TO_B,
+            p_b_to_g: P_B_TO_G,
+            processing_delay: PROCESSING_DELAY,
+        }
+    }
+}
+
+impl ChannelParams {
+    /// Starts from [`ChannelParams::default`]; chain `with_*` calls to
+    /// override only the fields a sweep cares about.
+    pub fn builder() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the bit rate.
+    pub fn with_bit_rate(mut self, bit_rate: i64) -> Self {
+        self.bit_rate = bit_rate;
+        self
+    }
+
+    /// Overrides the good-state BER.
+    pub fn with_good_state_ber(mut self, ber: f64) -> Self {
+        self.good_state_ber = ber;
+        self
+    }
+
+    /// Overrides the bad-state BER.
+    pub fn with_bad_state_ber(mut self, ber: f64) -> Self {
+        self.bad_state_ber = ber;
+        self
+    }
+
+    /// Overrides the good-to-bad transition probability.
+    pub fn with_p_g_to_b(mut self, p: f64) -> Self {
+        self.p_g_to_b = p;
+        self
+    }
+
+    /// Overrides the bad-to-good transition probability.
+    pub fn with_p_b_to_g(mut self, p: f64) -> Self {
+        self.p_b_to_g = p;
+        self
+    }
+
+    /// Overrides the per-frame processing delay.
+    pub fn with_processing_delay(mut self, delay: f64) -> Self {
+        self.processing_delay = delay;
+        self
+    }
+}
+
+/// Propagation-delay parameters for a simplex link.
+///
+/// Bundles the forward and reverse path delays so a sweep over link
+/// distance doesn't require a recompile either.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkParams {
+    /// Forward path propagation delay.
+    pub forward_path: f64,
+    /// Reverse (ACK) path propagation delay.
+    pub reverse_path: f64,
+}
+
+impl Default for LinkParams {
+    fn default() -> Self {
+        Self {
+            forward_path: FORWARD_PATH,
+            reverse_path: REVERSE_PATH,
+        }
+    }
+}
+
+impl LinkParams {
+    /// Starts from [`LinkParams::default`]; chain `with_*` calls to
+    /// override only the fields a sweep cares about.
+    pub fn builder() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the forward path propagation delay.
+    pub fn with_forward_path(mut self, delay: f64) -> Self {
+        self.forward_path = delay;
+        self
+    }
+
+    /// Overrides the reverse path propagation delay.
+    pub fn with_reverse_path(mut self, delay: f64) -> Self {
+        self.reverse_path = delay;
+        self
+    }
+}
@@ -0,0 +1,230 @@
+/// Initial congestion window, in MSS segments.
+static INITIAL_CWND_SEGMENTS: f64 = 4.0;
+
+/// Floor applied to `ssthresh` after a timeout, in MSS segments.
+static MIN_SSTHRESH_SEGMENTS: f64 = 2.0;
+
+/// CUBIC's window-scaling constant.
+static CUBIC_C: f64 = 0.4;
+
+/// CUBIC's multiplicative window reduction on loss.
+static CUBIC_BETA: f64 = 0.7;
+
+/// A pluggable congestion-control strategy consulted by [`super::layers::link::Sender`]
+/// to cap its effective send window independently of the receiver-advertised
+/// window. All state is tracked in bytes, consistent with TCP congestion
+/// control; [`Sender`](super::layers::link::Sender) converts to frames via its `mss`.
+pub trait CongestionControl {
+    /// Called when an ACK for `seq` arrives at simulated time `now`,
+    /// acknowledging `bytes` bytes `rtt` seconds after they were sent.
+    fn on_ack(&mut self, seq: i64, bytes: usize, rtt: f64, now: f64);
+
+    /// Called when `seq` is detected lost via a duplicate-Srej/fast-retransmit
+    /// trigger at simulated time `now`, distinct from a full retransmission
+    /// timeout.
+    fn on_loss(&mut self, seq: i64, now: f64);
+
+    /// Called when a frame's retransmission timer fires at simulated time `now`.
+    fn on_timeout(&mut self, now: f64);
+
+    /// Current congestion window, in bytes.
+    fn cwnd_bytes(&self) -> f64;
+}
+
+/// Fixed congestion window that never shrinks or grows.
+///
+/// Used to recover the pre-congestion-control behavior, where the
+/// receiver-advertised window was the only cap on the sender, for
+/// goodput comparisons against [`NewReno`]/[`Cubic`].
+pub struct FixedWindow {
+    cwnd: f64,
+}
+
+impl FixedWindow {
+    /// Creates a fixed window wide enough to never constrain the sender
+    /// below its receiver-advertised window.
+    pub fn new() -> Self {
+        Self { cwnd: f64::MAX }
+    }
+}
+
+impl Default for FixedWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CongestionControl for FixedWindow {
+    fn on_ack(&mut self, _seq: i64, _bytes: usize, _rtt: f64, _now: f64) {}
+
+    fn on_loss(&mut self, _seq: i64, _now: f64) {}
+
+    fn on_timeout(&mut self, _now: f64) {}
+
+    fn cwnd_bytes(&self) -> f64 {
+        self.cwnd
+    }
+}
+
+/// TCP New Reno congestion control, tracking `cwnd`/`ssthresh` in bytes.
+///
+/// Slow start grows `cwnd` by one `mss` per ACK; congestion avoidance
+/// (once `cwnd >= ssthresh`) grows it by `mss * mss / cwnd` per ACK, the
+/// standard approximation of +1 MSS per RTT. A timeout sets `ssthresh` to
+/// half of `cwnd` (floored at `2 * mss`) and resets `cwnd` to the initial
+/// window, re-entering slow start. A fast-retransmit loss instead sets
+/// `ssthresh` to half of `cwnd` and `cwnd` to that same value (fast
+/// recovery), without the full slow-start restart a timeout implies.
+pub struct NewReno {
+    cwnd: f64,
+    ssthresh: f64,
+    mss: f64,
+    initial_cwnd: f64,
+}
+
+impl NewReno {
+    /// Creates a New Reno controller for a link with the given maximum
+    /// segment size, in bytes.
+    pub fn new(mss: usize) -> Self {
+        let mss = mss as f64;
+        let initial_cwnd = mss * INITIAL_CWND_SEGMENTS;
+
+        Self {
+            cwnd: initial_cwnd,
+            ssthresh: f64::MAX,
+            mss,
+            initial_cwnd,
+        }
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn on_ack(&mut self, _seq: i64, _bytes: usize, _rtt: f64, _now: f64) {
+        if self.cwnd < self.ssthresh {
+            self.cwnd += self.mss; // slow start
+        } else {
+            self.cwnd += self.mss * self.mss / self.cwnd; // congestion avoidance
+        }
+    }
+
+    fn on_loss(&mut self, _seq: i64, _now: f64) {
+        // Fast retransmit / fast recovery: halve without restarting slow start.
+        self.ssthresh = self.cwnd / 2.0;
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _now: f64) {
+        self.ssthresh = (self.cwnd / 2.0).max(MIN_SSTHRESH_SEGMENTS * self.mss);
+        self.cwnd = self.initial_cwnd;
+    }
+
+    fn cwnd_bytes(&self) -> f64 {
+        self.cwnd
+    }
+}
+
+/// CUBIC congestion control, growing `cwnd` as a cubic function of time
+/// since the last loss rather than one ACK-count step at a time, which
+/// keeps it scaling on high-bandwidth-delay-product links where Reno's
+/// one-MSS-per-RTT growth takes too long to reclaim lost capacity.
+///
+/// `cwnd` follows `W(t) = C*(t - K)^3 + w_max`, where `t` is the time
+/// since the current epoch began, `w_max` is the window at the last loss,
+/// and `K = cbrt(w_max * beta / C)`, which puts `W(0)` at
+/// `w_max * (1 - beta)`, below the `w_max * beta` window left by the
+/// multiplicative cut. A loss records
+/// `w_max`, cuts `cwnd` by `beta`, and starts a new epoch. Since the cubic
+/// curve grows slower than Reno at small windows, every ACK also computes
+/// a Reno-equivalent AIMD estimate and takes the larger of the two (the
+/// "TCP-friendly region"), so CUBIC never underperforms Reno at low BDP.
+pub struct Cubic {
+    cwnd: f64,
+    w_max: f64,
+    /// Epoch start, in simulated seconds.
+    t0: f64,
+    /// Cached cube root for the current epoch's `w_max`.
+    k: f64,
+    mss: f64,
+    initial_cwnd: f64,
+}
+
+impl Cubic {
+    /// Creates a CUBIC controller for a link with the given maximum
+    /// segment size, in bytes.
+    pub fn new(mss: usize) -> Self {
+        let mss = mss as f64;
+        let initial_cwnd = mss * INITIAL_CWND_SEGMENTS;
+
+        let mut cubic = Self {
+            cwnd: initial_cwnd,
+            w_max: initial_cwnd,
+            t0: 0.0,
+            k: 0.0,
+            mss,
+            initial_cwnd,
+        };
+        cubic.recompute_k();
+        cubic
+    }
+
+    fn recompute_k(&mut self) {
+        self.k = (self.w_max * CUBIC_BETA / CUBIC_C).cbrt();
+    }
+}
+
+impl CongestionControl for Cubic {
+    fn on_ack(&mut self, _seq: i64, _bytes: usize, rtt: f64, now: f64) {
+        let t = now - self.t0;
+        let cubic_target = CUBIC_C * (t - self.k).powi(3) + self.w_max;
+
+        // Reno-equivalent AIMD estimate: starting from the post-cut window,
+        // grow by one MSS per RTT elapsed in this epoch.
+        let rtt = rtt.max(f64::EPSILON);
+        let reno_estimate = self.w_max * CUBIC_BETA + (t / rtt) * self.mss;
+
+        self.cwnd = cubic_target.max(reno_estimate);
+    }
+
+    fn on_loss(&mut self, _seq: i64, now: f64) {
+        self.w_max = self.cwnd;
+        self.cwnd *= CUBIC_BETA;
+        self.t0 = now;
+        self.recompute_k();
+    }
+
+    fn on_timeout(&mut self, now: f64) {
+        self.w_max = self.cwnd;
+        self.cwnd = self.initial_cwnd;
+        self.t0 = now;
+        self.recompute_k();
+    }
+
+    fn cwnd_bytes(&self) -> f64 {
+        self.cwnd
+    }
+}
+
+/// Selects which [`CongestionControl`] implementation to build, deferring
+/// construction until the maximum segment size is known (e.g. once a
+/// simulation's frame payload size is chosen).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CongestionStrategy {
+    /// No dynamic congestion window; see [`FixedWindow`].
+    Fixed,
+    /// TCP New Reno; see [`NewReno`].
+    NewReno,
+    /// CUBIC; see [`Cubic`].
+    Cubic,
+}
+
+impl CongestionStrategy {
+    /// Builds the selected controller for a link with the given maximum
+    /// segment size, in bytes.
+    pub fn build(self, mss: usize) -> Box<dyn CongestionControl + Send> {
+        match self {
+            Self::Fixed => Box::new(FixedWindow::new()),
+            Self::NewReno => Box::new(NewReno::new(mss)),
+            Self::Cubic => Box::new(Cubic::new(mss)),
+        }
+    }
+}
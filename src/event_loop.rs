@@ -3,21 +3,112 @@ use std::{
     collections::{BTreeSet, BinaryHeap},
     future::Future,
     pin::Pin,
+    sync::Arc,
 };
+use futures::{Stream, stream};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use tokio::sync::Mutex;
 
 /// A future type that can be scheculed.
 pub type EventFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
+/// A node in a hierarchical cancellation tree, borrowed from tokio-util's
+/// `CancellationToken`. Cancelling a token also cancels every descendant
+/// spawned via [`CancellationToken::child_token`], so tearing down every
+/// timer belonging to one connection or flow is a single call instead of
+/// tracking each event id by hand.
+struct CancellationNode {
+    cancelled: bool,
+    parent: Option<Arc<Mutex<CancellationNode>>>,
+    children: Vec<Arc<Mutex<CancellationNode>>>,
+}
+
+/// A handle into a [`CancellationNode`] tree; see [`EventLoop::schedule_with_token`]
+/// and [`EventLoop::cancel_token`].
+#[derive(Clone)]
+pub struct CancellationToken {
+    node: Arc<Mutex<CancellationNode>>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    /// Creates a new, un-cancelled root token.
+    pub fn new() -> Self {
+        Self {
+            node: Arc::new(Mutex::new(CancellationNode {
+                cancelled: false,
+                parent: None,
+                children: Vec::new(),
+            })),
+        }
+    }
+
+    /// Creates a child token scoped under `self`: cancelling `self` (or
+    /// any of its ancestors) cancels this child too, but cancelling the
+    /// child has no effect on `self`. Use one child per flow/frame so a
+    /// connection teardown can cancel the whole subtree in one call.
+    pub async fn child_token(&self) -> CancellationToken {
+        let child = Arc::new(Mutex::new(CancellationNode {
+            cancelled: false,
+            parent: Some(self.node.clone()),
+            children: Vec::new(),
+        }));
+
+        self.node.lock().await.children.push(child.clone());
+
+        CancellationToken { node: child }
+    }
+
+    /// Cancels this token and every descendant spawned via
+    /// [`Self::child_token`], iteratively so no recursive `async fn` is
+    /// needed.
+    pub async fn cancel(&self) {
+        let mut stack = vec![self.node.clone()];
+
+        while let Some(node) = stack.pop() {
+            let mut node = node.lock().await;
+            node.cancelled = true;
+            stack.extend(node.children.iter().cloned());
+        }
+    }
+
+    /// Whether this token, or any ancestor up to the root, has been
+    /// cancelled.
+    pub async fn is_cancelled(&self) -> bool {
+        let mut current = Some(self.node.clone());
+
+        while let Some(node) = current {
+            let node = node.lock().await;
+
+            if node.cancelled {
+                return true;
+            }
+
+            current = node.parent.clone();
+        }
+
+        false
+    }
+}
+
 struct Event {
     time: f64,
     id: i64,
     event: EventFuture,
+    /// Cancellation token checked by [`EventLoop::advance`] in addition
+    /// to the flat id-based lookup; `None` for events scheduled via
+    /// [`EventLoop::schedule`]/[`EventLoop::schedule_after`].
+    token: Option<CancellationToken>,
 }
 
 impl PartialEq for Event {
     fn eq(&self, other: &Self) -> bool {
-        other.time == self.time
+        other.time == self.time && other.id == self.id
     }
 }
 
@@ -25,7 +116,13 @@ impl Eq for Event {}
 
 impl Ord for Event {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.time.total_cmp(&self.time)
+        // Reversed so `BinaryHeap` (a max-heap) pops the smallest time
+        // first; ties break on the reversed id so equal-time events pop
+        // in insertion (FIFO) order instead of arbitrary heap order.
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| other.id.cmp(&self.id))
     }
 }
 
@@ -36,23 +133,59 @@ impl PartialOrd for Event {
 }
 
 /// An event loop implementation for discrete time simulation.
+///
+/// All methods take `&self`, backed by internal `Mutex`es, so the loop is
+/// meant to be wrapped in `Arc<EventLoop>` and cloned into every scheduled
+/// event future. That lets an event running inside [`Self::advance`]
+/// schedule or cancel further events on the same loop — e.g. a
+/// retransmission timeout that reschedules itself on loss — without
+/// threading a mutable borrow through the closure.
 pub struct EventLoop {
     events: Mutex<BinaryHeap<Event>>,
     cancelled_events: Mutex<BTreeSet<i64>>,
     event_id: Mutex<i64>,
+    /// Virtual simulation clock: the time of the most recently popped
+    /// event, so an event future can ask "what time is it now?" via
+    /// [`Self::now`] instead of threading its own send time through.
+    current_time: Mutex<f64>,
+    /// Seeded from the loop's own seed via [`Self::new_seeded`], this
+    /// draws the per-component seeds handed out by [`Self::next_seed`],
+    /// so an entire simulation's stochastic event timing is determined
+    /// by one top-level seed instead of each component reaching for OS
+    /// entropy independently.
+    rng: Mutex<StdRng>,
 }
 
 impl Default for EventLoop {
     fn default() -> Self {
+        Self::new_seeded(rand::rng().random())
+    }
+}
+
+impl EventLoop {
+    /// Creates a new event loop seeded from `seed`. Components that need
+    /// their own RNG (e.g. a [`crate::channel::ChannelModel`]) should
+    /// draw a sub-seed via [`Self::next_seed`] rather than seeding from
+    /// OS entropy, so the whole run is reproducible from `seed` alone.
+    pub fn new_seeded(seed: u64) -> Self {
         Self {
             events: Mutex::new(BinaryHeap::new()),
             cancelled_events: Mutex::new(BTreeSet::new()),
             event_id: Mutex::new(0),
+            current_time: Mutex::new(0.0),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
         }
     }
-}
 
-impl EventLoop {
+    /// Draws the next sub-seed from the loop's own seeded RNG, for a
+    /// component (e.g. a channel model or timer jitter source) to seed
+    /// itself with. Repeated calls on a loop constructed with the same
+    /// seed hand out the same sequence of sub-seeds, so a one-seed
+    /// Monte-Carlo sweep reproduces every component's randomness.
+    pub async fn next_seed(&self) -> u64 {
+        self.rng.lock().await.random()
+    }
+
     /// Run next event in the loop
     pub async fn advance(&self) {
         let has_been_cancelled;
@@ -71,32 +204,152 @@ impl EventLoop {
             has_been_cancelled = cancelled_events.remove(&event.id);
         }
 
-        if !has_been_cancelled {
+        *self.current_time.lock().await = event.time;
+
+        let token_cancelled = match &event.token {
+            Some(token) => token.is_cancelled().await,
+            None => false,
+        };
+
+        if !has_been_cancelled && !token_cancelled {
             event.event.await
         }
     }
 
     /// Cancels event with given id
-    pub async fn cancel(&mut self, event_id: i64) {
+    pub async fn cancel(&self, event_id: i64) {
         self.cancelled_events.lock().await.insert(event_id);
     }
 
+    /// Cancels every event scheduled with `token` (via
+    /// [`Self::schedule_with_token`]), or with any token descended from
+    /// it, in one call.
+    pub async fn cancel_token(&self, token: &CancellationToken) {
+        token.cancel().await;
+    }
+
     /// Schedules a new event
-    pub async fn schedule(&mut self, event: EventFuture, time: f64) -> i64 {
+    pub async fn schedule(&self, event: EventFuture, time: f64) -> i64 {
         let mut events = self.events.lock().await;
         let mut event_id = self.event_id.lock().await;
 
         let id = *event_id;
-        events.push(Event { time, id, event });
+        events.push(Event {
+            time,
+            id,
+            event,
+            token: None,
+        });
 
         *event_id += 1;
         id
     }
 
+    /// Schedules `event` to run `delay` virtual seconds from now, i.e. at
+    /// `self.now().await + delay`.
+    pub async fn schedule_after(&self, event: EventFuture, delay: f64) -> i64 {
+        let time = self.now().await + delay;
+
+        let mut events = self.events.lock().await;
+        let mut event_id = self.event_id.lock().await;
+
+        let id = *event_id;
+        events.push(Event {
+            time,
+            id,
+            event,
+            token: None,
+        });
+
+        *event_id += 1;
+        id
+    }
+
+    /// Schedules `event` at `time`, tied to `token`: [`Self::cancel_token`]
+    /// on `token` (or on any of its ancestors) prevents this event from
+    /// running, without needing to track its id.
+    pub async fn schedule_with_token(
+        &self,
+        event: EventFuture,
+        time: f64,
+        token: CancellationToken,
+    ) -> i64 {
+        let mut events = self.events.lock().await;
+        let mut event_id = self.event_id.lock().await;
+
+        let id = *event_id;
+        events.push(Event {
+            time,
+            id,
+            event,
+            token: Some(token),
+        });
+
+        *event_id += 1;
+        id
+    }
+
+    /// Returns the virtual simulation clock: the time of the most
+    /// recently popped event, advanced monotonically by [`Self::advance`].
+    pub async fn now(&self) -> f64 {
+        *self.current_time.lock().await
+    }
+
     /// Returns number of pending events
     pub async fn pending_count(&self) -> usize {
         self.events.lock().await.len()
     }
+
+    /// Advances the loop until no events remain pending, including any
+    /// rescheduled from within [`Self::advance`] (e.g. a retransmission
+    /// timer rearming itself), so callers don't have to write a manual
+    /// `while pending_count() > 0` loop.
+    pub async fn run(&self) {
+        while self.pending_count().await > 0 {
+            self.advance().await;
+        }
+    }
+
+    /// Advances the loop while the next pending event's time does not
+    /// exceed `t`, leaving any later event queued. Never executes the
+    /// event that would cross `t`; the clock is left at the last
+    /// executed event's time, not at `t` itself.
+    pub async fn run_until(&self, t: f64) {
+        loop {
+            let next_time = {
+                let events = self.events.lock().await;
+                events.peek().map(|event| event.time)
+            };
+
+            match next_time {
+                Some(time) if time <= t => self.advance().await,
+                _ => return,
+            }
+        }
+    }
+
+    /// Like [`Self::run_until`], but `horizon` is relative to
+    /// [`Self::now`] rather than an absolute virtual time.
+    pub async fn run_for(&self, horizon: f64) {
+        let deadline = self.now().await + horizon;
+        self.run_until(deadline).await;
+    }
+
+    /// Returns a [`Stream`] that advances the loop one event at a time,
+    /// yielding `()` per completed [`Self::advance`] call until the
+    /// queue is empty. Lets callers compose the simulation with
+    /// `.take(n)`, `.for_each`, or `select!` against other async
+    /// sources instead of writing a manual drain loop.
+    pub fn events(&self) -> impl Stream<Item = ()> + '_ {
+        stream::unfold(self, |event_loop| async move {
+            if event_loop.pending_count().await == 0 {
+                None
+            } else {
+                event_loop.advance().await;
+                Some(((), event_loop))
+            }
+        })
+    }
 }
 
 #[cfg(test)]
@@ -108,7 +361,7 @@ mod tests {
     #[tokio::test]
     #[test_log::test]
     async fn test_schedule_and_advance() {
-        let mut event_loop = EventLoop::default();
+        let event_loop = EventLoop::default();
         let executed = Arc::new(TokioMutex::new(false));
 
         let executed_clone = executed.clone();
@@ -131,7 +384,7 @@ mod tests {
     #[tokio::test]
     #[test_log::test]
     async fn test_event_ordering() {
-        let mut event_loop = EventLoop::default();
+        let event_loop = EventLoop::default();
         let order = Arc::new(TokioMutex::new(Vec::new()));
 
         // Schedule events out of order
@@ -177,7 +430,7 @@ mod tests {
     #[tokio::test]
     #[test_log::test]
     async fn test_event_cancellation() {
-        let mut event_loop = EventLoop::default();
+        let event_loop = EventLoop::default();
         let executed = Arc::new(TokioMutex::new(Vec::new()));
 
         let executed_clone = executed.clone();
@@ -214,7 +467,7 @@ mod tests {
     #[tokio::test]
     #[test_log::test]
     async fn test_multiple_events_same_time() {
-        let mut event_loop = EventLoop::default();
+        let event_loop = EventLoop::default();
         let executed = Arc::new(TokioMutex::new(Vec::new()));
 
         // Schedule multiple events at same time
@@ -241,7 +494,7 @@ mod tests {
     #[tokio::test]
     #[test_log::test]
     async fn test_pending_count() {
-        let mut event_loop = EventLoop::default();
+        let event_loop = EventLoop::default();
 
         assert_eq!(event_loop.pending_count().await, 0);
 
@@ -269,4 +522,325 @@ mod tests {
 
         assert_eq!(event_loop.pending_count().await, 0);
     }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_now_tracks_popped_event_time() {
+        let event_loop = EventLoop::default();
+
+        assert_eq!(event_loop.now().await, 0.0);
+
+        event_loop.schedule(Box::pin(async {}), 1.5).await;
+        event_loop.schedule(Box::pin(async {}), 3.0).await;
+
+        event_loop.advance().await;
+        assert_eq!(event_loop.now().await, 1.5);
+
+        event_loop.advance().await;
+        assert_eq!(event_loop.now().await, 3.0);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_schedule_after_is_relative_to_now() {
+        let event_loop = EventLoop::default();
+
+        event_loop.schedule(Box::pin(async {}), 2.0).await;
+        event_loop.advance().await;
+        assert_eq!(event_loop.now().await, 2.0);
+
+        let order = Arc::new(TokioMutex::new(Vec::new()));
+        let order_clone = order.clone();
+        event_loop
+            .schedule_after(
+                Box::pin(async move {
+                    order_clone.lock().await.push(());
+                }),
+                0.5,
+            )
+            .await;
+
+        // Scheduled at now() + delay == 2.5, so it must still be pending
+        // just short of that time.
+        assert_eq!(event_loop.pending_count().await, 1);
+
+        event_loop.advance().await;
+        assert_eq!(event_loop.now().await, 2.5);
+        assert_eq!(order.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_event_can_reschedule_itself() {
+        let event_loop = Arc::new(EventLoop::default());
+        let fired = Arc::new(TokioMutex::new(0));
+
+        fn reschedule(event_loop: Arc<EventLoop>, fired: Arc<TokioMutex<u64>>) -> EventFuture {
+            Box::pin(async move {
+                *fired.lock().await += 1;
+
+                if *fired.lock().await < 3 {
+                    event_loop
+                        .schedule_after(reschedule(event_loop.clone(), fired.clone()), 1.0)
+                        .await;
+                }
+            })
+        }
+
+        event_loop
+            .schedule(reschedule(event_loop.clone(), fired.clone()), 1.0)
+            .await;
+
+        event_loop.advance().await;
+        event_loop.advance().await;
+        event_loop.advance().await;
+
+        assert_eq!(*fired.lock().await, 3);
+        assert_eq!(event_loop.now().await, 3.0);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_equal_time_events_pop_in_fifo_order() {
+        let event_loop = EventLoop::default();
+        let order = Arc::new(TokioMutex::new(Vec::new()));
+
+        for i in 0..5 {
+            let order_clone = order.clone();
+            event_loop
+                .schedule(
+                    Box::pin(async move {
+                        order_clone.lock().await.push(i);
+                    }),
+                    1.0,
+                )
+                .await;
+        }
+
+        for _ in 0..5 {
+            event_loop.advance().await;
+        }
+
+        let result = order.lock().await;
+        assert_eq!(*result, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_cancel_token_stops_scheduled_event() {
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(false));
+
+        let token = CancellationToken::new();
+        let executed_clone = executed.clone();
+        event_loop
+            .schedule_with_token(
+                Box::pin(async move {
+                    *executed_clone.lock().await = true;
+                }),
+                1.0,
+                token.clone(),
+            )
+            .await;
+
+        event_loop.cancel_token(&token).await;
+        event_loop.advance().await;
+
+        assert!(!*executed.lock().await);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_cancel_token_cancels_descendant_tokens() {
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(Vec::new()));
+
+        let parent = CancellationToken::new();
+        let child = parent.child_token().await;
+        let grandchild = child.child_token().await;
+
+        let executed_clone = executed.clone();
+        event_loop
+            .schedule_with_token(
+                Box::pin(async move {
+                    executed_clone.lock().await.push(1);
+                }),
+                1.0,
+                child.clone(),
+            )
+            .await;
+
+        let executed_clone = executed.clone();
+        event_loop
+            .schedule_with_token(
+                Box::pin(async move {
+                    executed_clone.lock().await.push(2);
+                }),
+                2.0,
+                grandchild,
+            )
+            .await;
+
+        assert!(!child.is_cancelled().await);
+
+        // Cancelling the root must cancel every descendant token.
+        event_loop.cancel_token(&parent).await;
+
+        assert!(child.is_cancelled().await);
+
+        event_loop.advance().await;
+        event_loop.advance().await;
+
+        assert!(executed.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_cancel_token_does_not_affect_siblings_or_parent() {
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(Vec::new()));
+
+        let parent = CancellationToken::new();
+        let sibling_a = parent.child_token().await;
+        let sibling_b = parent.child_token().await;
+
+        let executed_clone = executed.clone();
+        event_loop
+            .schedule_with_token(
+                Box::pin(async move {
+                    executed_clone.lock().await.push(1);
+                }),
+                1.0,
+                sibling_a.clone(),
+            )
+            .await;
+
+        let executed_clone = executed.clone();
+        event_loop
+            .schedule_with_token(
+                Box::pin(async move {
+                    executed_clone.lock().await.push(2);
+                }),
+                2.0,
+                sibling_b,
+            )
+            .await;
+
+        event_loop.cancel_token(&sibling_a).await;
+
+        assert!(!parent.is_cancelled().await);
+
+        event_loop.advance().await;
+        event_loop.advance().await;
+
+        let result = executed.lock().await;
+        assert_eq!(*result, vec![2]);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_run_drains_all_events() {
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(Vec::new()));
+
+        for i in 0..3 {
+            let executed_clone = executed.clone();
+            event_loop
+                .schedule(
+                    Box::pin(async move {
+                        executed_clone.lock().await.push(i);
+                    }),
+                    i as f64,
+                )
+                .await;
+        }
+
+        event_loop.run().await;
+
+        assert_eq!(event_loop.pending_count().await, 0);
+        assert_eq!(*executed.lock().await, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_run_until_stops_before_boundary_event() {
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(Vec::new()));
+
+        for &time in &[1.0, 2.0, 3.0] {
+            let executed_clone = executed.clone();
+            event_loop
+                .schedule(
+                    Box::pin(async move {
+                        executed_clone.lock().await.push(time as i64);
+                    }),
+                    time,
+                )
+                .await;
+        }
+
+        event_loop.run_until(2.0).await;
+
+        // The event at t=3.0 must not run, and the clock must sit at the
+        // last executed event's time (2.0), not the boundary.
+        assert_eq!(*executed.lock().await, vec![1, 2]);
+        assert_eq!(event_loop.now().await, 2.0);
+        assert_eq!(event_loop.pending_count().await, 1);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_run_for_is_relative_to_now() {
+        let event_loop = EventLoop::default();
+
+        event_loop.schedule(Box::pin(async {}), 1.0).await;
+        event_loop.advance().await;
+        assert_eq!(event_loop.now().await, 1.0);
+
+        event_loop.schedule(Box::pin(async {}), 1.5).await;
+        event_loop.schedule(Box::pin(async {}), 3.0).await;
+
+        // now() == 1.0, so a horizon of 1.0 only admits the t=1.5 event.
+        event_loop.run_for(1.0).await;
+
+        assert_eq!(event_loop.now().await, 1.5);
+        assert_eq!(event_loop.pending_count().await, 1);
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_new_seeded_reproduces_sub_seed_sequence() {
+        let a = EventLoop::new_seeded(7);
+        let b = EventLoop::new_seeded(7);
+
+        for _ in 0..5 {
+            assert_eq!(a.next_seed().await, b.next_seed().await);
+        }
+    }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_events_stream_yields_once_per_advance() {
+        use futures::StreamExt;
+
+        let event_loop = EventLoop::default();
+        let executed = Arc::new(TokioMutex::new(Vec::new()));
+
+        for i in 0..3 {
+            let executed_clone = executed.clone();
+            event_loop
+                .schedule(
+                    Box::pin(async move {
+                        executed_clone.lock().await.push(i);
+                    }),
+                    i as f64,
+                )
+                .await;
+        }
+
+        let yielded = event_loop.events().count().await;
+
+        assert_eq!(yielded, 3);
+        assert_eq!(*executed.lock().await, vec![0, 1, 2]);
+    }
 }
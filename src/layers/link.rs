@@ -1,12 +1,150 @@
-use std::{collections::HashMap, sync::Arc};
-
-use crate::{common::*, event_loop::EventLoop};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    common::*,
+    congestion::{CongestionControl, CongestionStrategy},
+    event_loop::{EventFuture, EventLoop},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use tokio::sync::Mutex;
 use tracing::{debug, instrument, trace};
 
 use super::physical::{Frame, SimplexChannel};
+use super::range_tracker::RangeTracker;
 
 const RECEIVER_BUFFER_SIZE: usize = 256 * 1024; // 256 KB
+/// Maximum number of ranges a `Frame::Sack` reports at once.
+const MAX_SACK_RANGES: usize = 8;
+/// Number of duplicate hole reports for the same unresolved sequence
+/// number (successive `Sack`s confirming it's still missing) required
+/// before `Sender::handle_sack` treats it as a loss and fast-retransmits,
+/// mirroring TCP's three-duplicate-ACK rule.
+const DUP_ACK_THRESHOLD: u32 = 3;
+/// Check-sequence width, in bits, used by [`FrameCheck::new_seeded_crc16`]:
+/// a CRC-16.
+const CRC16_BITS: u32 = 16;
+/// Check-sequence width, in bits, used by [`FrameCheck::new_seeded_crc32`]:
+/// a CRC-32.
+const CRC32_BITS: u32 = 32;
+
+/// SRTT gain (Jacobson's algorithm, the standard `1/8`).
+static SRTT_ALPHA: f64 = 1.0 / 8.0;
+/// RTTVAR gain (Jacobson's algorithm, the standard `1/4`).
+static RTTVAR_BETA: f64 = 1.0 / 4.0;
+/// RTTVAR multiplier applied in the RTO formula.
+static RTTVAR_K: f64 = 4.0;
+/// Floor applied to the RTO estimate, before per-frame backoff.
+static MIN_RTO: f64 = 0.01;
+/// Ceiling applied to the RTO estimate, after per-frame backoff.
+static MAX_RTO: f64 = 60.0;
+
+/// Outcome of a frame's error-detection check, distinguishing a clean
+/// frame from one whose corruption the check sequence caught versus one
+/// whose corruption slipped through undetected.
+///
+/// Collapsing these into a single "corrupted" bool hides an ARQ
+/// simulator's whole point: what fraction of frames are wrong in a way
+/// the receiver can't even tell, after the check sequence has already
+/// done its job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOutcome {
+    /// No bits were flipped.
+    Clean,
+    /// At least one bit was flipped and the check sequence caught it;
+    /// the transport layer should treat this exactly like a NAK'd frame
+    /// (drop it and wait for a retransmit).
+    DetectedError,
+    /// At least one bit was flipped, but it happened to collide with
+    /// another valid codeword in the check sequence's residue space, so
+    /// the frame looks clean to the receiver. This is the residual,
+    /// post-ARQ error rate that survives retransmission entirely.
+    UndetectedError,
+}
+
+/// CRC-style frame-check subsystem, consulted once a frame has (or
+/// hasn't) been corrupted, to decide whether its check sequence would
+/// have caught that corruption.
+///
+/// An `r`-bit check sequence has `2^r` possible residues, so once a
+/// frame is corrupted at all, it collides with another valid codeword
+/// (an undetected error) with probability `2^-r`, independent of how
+/// many bits actually flipped.
+pub struct FrameCheck {
+    rng: StdRng,
+    crc_bits: u32,
+}
+
+impl FrameCheck {
+    /// Creates a new frame check using an arbitrary `crc_bits`-bit check
+    /// sequence, seeded for reproducibility.
+    pub fn new_seeded(seed: u64, crc_bits: u32) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            crc_bits,
+        }
+    }
+
+    /// Creates a new frame check using a CRC-16-sized check sequence.
+    pub fn new_seeded_crc16(seed: u64) -> Self {
+        Self::new_seeded(seed, CRC16_BITS)
+    }
+
+    /// Creates a new frame check using a CRC-32-sized check sequence.
+    pub fn new_seeded_crc32(seed: u64) -> Self {
+        Self::new_seeded(seed, CRC32_BITS)
+    }
+
+    /// Checks a frame of `num_bits` sent over a channel with the given
+    /// per-bit `ber`, returning the resulting [`FrameOutcome`].
+    ///
+    /// Whether any bit flipped is drawn from the complement of the
+    /// binomial probability of zero flips in `num_bits` trials at `ber`
+    /// (`1 - (1 - ber)^num_bits`) rather than a per-bit loop, the same
+    /// jump-ahead-friendly shortcut [`crate::channel::MarkovChannel`]
+    /// uses. If at least one bit flipped, a second draw decides whether
+    /// the check sequence caught it, with probability `1 - 2^-r`.
+    pub fn frame_outcome(&mut self, num_bits: u64, ber: f64) -> FrameOutcome {
+        let p_no_flip = (1.0 - ber).powf(num_bits as f64);
+        let any_flipped = self.rng.random::<f64>() >= p_no_flip;
+
+        if !any_flipped {
+            return FrameOutcome::Clean;
+        }
+
+        self.resolve_detection()
+    }
+
+    /// Checks a frame whose corruption has already been decided by some
+    /// other bit-error model (e.g. [`crate::channel::ChannelModel`]'s
+    /// bursty Gilbert-Elliot state), returning whether this check
+    /// sequence would have caught it.
+    ///
+    /// Use this instead of [`Self::frame_outcome`] when the caller
+    /// already knows whether the frame flipped a bit and just needs the
+    /// detected/undetected split.
+    pub fn check(&mut self, corrupted: bool) -> FrameOutcome {
+        if !corrupted {
+            return FrameOutcome::Clean;
+        }
+
+        self.resolve_detection()
+    }
+
+    /// Draws whether an already-corrupted frame's check sequence caught
+    /// it, with probability `1 - 2^-r`.
+    fn resolve_detection(&mut self) -> FrameOutcome {
+        let p_detected = 1.0 - 2f64.powi(-(self.crc_bits as i32));
+
+        if self.rng.random::<f64>() < p_detected {
+            FrameOutcome::DetectedError
+        } else {
+            FrameOutcome::UndetectedError
+        }
+    }
+}
 
 /// Link layer sender state
 pub struct Sender {
@@ -14,32 +152,116 @@ pub struct Sender {
     base: i64,
     /// Next sequence number to send
     next_seq: i64,
-    /// Window size
-    window_size: i64,
+    /// Receiver-advertised window, in frames: an upper bound on the
+    /// effective window independent of congestion control.
+    rwnd: i64,
+    /// Maximum segment size, in bytes, used to convert the congestion
+    /// window from bytes to frames.
+    mss: usize,
     /// Buffer of sent but unacknowledged frames
     sent_frames: HashMap<i64, Vec<u8>>,
+    /// Time each outstanding frame was (first) sent, for RTT sampling on ACK
+    send_times: HashMap<i64, f64>,
     /// Active timer event IDs for each sequence number
     timers: HashMap<i64, i64>,
-    /// Timeout duration
-    timeout: f64,
+    /// Retransmission timeout estimate (Jacobson/Karn `SRTT + 4*RTTVAR`),
+    /// before any per-frame exponential backoff; seeded with a caller-
+    /// supplied estimate until the first sample updates it.
+    rto: f64,
+    /// Smoothed RTT estimate, `None` until the first sample.
+    srtt: Option<f64>,
+    /// Smoothed RTT variation estimate, `None` until the first sample.
+    rttvar: Option<f64>,
+    /// Per-outstanding-frame exponential backoff multiplier on `rto`,
+    /// doubled each time that frame's timer fires without an ACK and
+    /// cleared on a clean ACK.
+    backoff: HashMap<i64, f64>,
+    /// Outstanding frames that have been retransmitted at least once.
+    /// Karn's algorithm excludes their eventual ACK from RTT sampling,
+    /// since it can't tell which attempt the ACK is for.
+    retransmitted: HashSet<i64>,
+    /// Consecutive `Sack` reports confirming a given sequence number is
+    /// still an unresolved hole, since the last time it was acked or
+    /// fast-retransmitted. Cleared once `DUP_ACK_THRESHOLD` is reached.
+    hole_reports: HashMap<i64, u32>,
+    /// Congestion-control strategy capping the effective window alongside `rwnd`
+    congestion: Box<dyn CongestionControl + Send>,
 }
 
 impl Sender {
-    /// Creates a new sender
-    pub fn new(window_size: i64, timeout: f64) -> Self {
+    /// Creates a new sender using New Reno congestion control.
+    ///
+    /// `initial_rto` seeds the retransmission timeout estimate until the
+    /// first non-retransmitted ACK lets the SRTT/RTTVAR estimator take over.
+    pub fn new(window_size: i64, initial_rto: f64, mss: usize) -> Self {
+        Self::with_congestion_control(
+            window_size,
+            initial_rto,
+            mss,
+            CongestionStrategy::NewReno.build(mss),
+        )
+    }
+
+    /// Creates a new sender with an explicit congestion-control strategy.
+    pub fn with_congestion_control(
+        window_size: i64,
+        initial_rto: f64,
+        mss: usize,
+        congestion: Box<dyn CongestionControl + Send>,
+    ) -> Self {
         Self {
             base: 0,
             next_seq: 0,
-            window_size,
+            rwnd: window_size,
+            mss,
             sent_frames: HashMap::new(),
+            send_times: HashMap::new(),
             timers: HashMap::new(),
-            timeout,
+            rto: initial_rto.clamp(MIN_RTO, MAX_RTO),
+            srtt: None,
+            rttvar: None,
+            backoff: HashMap::new(),
+            retransmitted: HashSet::new(),
+            hole_reports: HashMap::new(),
+            congestion,
         }
     }
 
+    /// Effective send window, in frames: the smaller of the receiver-
+    /// advertised window and the congestion window, the latter converted
+    /// from bytes via `mss`.
+    fn effective_window(&self) -> i64 {
+        let cwnd_frames = (self.congestion.cwnd_bytes() / self.mss as f64).floor() as i64;
+        self.rwnd.min(cwnd_frames.max(1))
+    }
+
     /// Check if we can send more frames (window not full)
     pub fn can_send(&self) -> bool {
-        self.next_seq < self.base + self.window_size
+        self.next_seq < self.base + self.effective_window()
+    }
+
+    /// Retransmission timeout to arm `seq`'s timer with, applying its
+    /// exponential backoff multiplier (1.0 on first send, doubled on each
+    /// timer firing since) on top of the current RTO estimate.
+    pub fn rto_for(&self, seq: i64) -> f64 {
+        let backoff = self.backoff.get(&seq).copied().unwrap_or(1.0);
+        (self.rto * backoff).clamp(MIN_RTO, MAX_RTO)
+    }
+
+    /// Feeds a fresh (non-retransmitted) RTT sample into the SRTT/RTTVAR
+    /// estimator and recomputes `rto`.
+    fn sample_rtt(&mut self, rtt: f64) {
+        let (new_srtt, new_rttvar) = match (self.srtt, self.rttvar) {
+            (Some(s), Some(v)) => (
+                (1.0 - SRTT_ALPHA) * s + SRTT_ALPHA * rtt,
+                (1.0 - RTTVAR_BETA) * v + RTTVAR_BETA * (s - rtt).abs(),
+            ),
+            _ => (rtt, rtt / 2.0),
+        };
+
+        self.srtt = Some(new_srtt);
+        self.rttvar = Some(new_rttvar);
+        self.rto = (new_srtt + RTTVAR_K * new_rttvar).clamp(MIN_RTO, MAX_RTO);
     }
 
     /// Get next sequence number
@@ -48,18 +270,33 @@ impl Sender {
     }
 
     /// Store sent frame and return sequence number
-    pub fn send_frame(&mut self, data: Vec<u8>) -> i64 {
+    pub fn send_frame(&mut self, data: Vec<u8>, current_time: f64) -> i64 {
         let seq = self.next_seq;
+        self.send_times.insert(seq, current_time);
         self.sent_frames.insert(seq, data);
         self.next_seq += 1;
         seq
     }
 
-    /// Handle ACK - remove frame and slide window
-    pub fn handle_ack(&mut self, seq: i64) {
+    /// Handle ACK - remove frame, sample its RTT into congestion control
+    /// and (per Karn's algorithm, if this attempt wasn't a retransmission)
+    /// the RTO estimator, and slide the window
+    pub fn handle_ack(&mut self, seq: i64, current_time: f64) {
         trace!(seq, base = self.base, "Handling ACK");
         // Remove from sent frames
-        if self.sent_frames.remove(&seq).is_some() {
+        if let Some(data) = self.sent_frames.remove(&seq) {
+            if let Some(sent_at) = self.send_times.remove(&seq) {
+                let rtt = current_time - sent_at;
+                self.congestion.on_ack(seq, data.len(), rtt, current_time);
+
+                if !self.retransmitted.remove(&seq) {
+                    self.sample_rtt(rtt);
+                }
+            }
+
+            self.backoff.remove(&seq);
+            self.hole_reports.remove(&seq);
+
             // Slide window if this was the base
             while !self.sent_frames.contains_key(&self.base) && self.base < self.next_seq {
                 self.base += 1;
@@ -67,15 +304,89 @@ impl Sender {
         }
     }
 
-    /// Handle NAK - return frame data for retransmission
-    pub fn handle_nak(&self, seq: i64) -> Option<Vec<u8>> {
+    /// Handle NAK - notify congestion control of the loss and return frame
+    /// data for retransmission
+    pub fn handle_nak(&mut self, seq: i64, current_time: f64) -> Option<Vec<u8>> {
+        self.congestion.on_loss(seq, current_time);
+        self.retransmitted.insert(seq);
         self.sent_frames.get(&seq).cloned()
     }
 
-    /// Get frame for timeout retransmission
-    pub fn get_frame_for_timeout(&self, seq: i64) -> Option<Vec<u8>> {
+    /// Get frame for timeout retransmission, notifying congestion control
+    /// of the timeout and doubling `seq`'s backoff multiplier for its next
+    /// timer.
+    pub fn get_frame_for_timeout(&mut self, seq: i64, current_time: f64) -> Option<Vec<u8>> {
+        self.congestion.on_timeout(current_time);
+        self.retransmitted.insert(seq);
+
+        let backoff = self.backoff.entry(seq).or_insert(1.0);
+        *backoff *= 2.0;
+
         self.sent_frames.get(&seq).cloned()
     }
+
+    /// Handle a selective ACK: `base` is the cumulative ack point (every
+    /// sequence number below it is acknowledged, exactly like `handle_ack`
+    /// would do one at a time) and `ranges` are additional disjoint
+    /// `[start, end)` windows the receiver confirms it also has, even
+    /// though they're above `base`. Every remaining sequence number
+    /// between `base` and the top of `ranges` is a hole, not just a
+    /// presumed one, but is only returned for retransmission once
+    /// `DUP_ACK_THRESHOLD` successive `Sack`s have confirmed it (see
+    /// [`Self::confirm_hole`]), mirroring TCP's duplicate-ACK rule.
+    pub fn handle_sack(
+        &mut self,
+        base: i64,
+        ranges: &[(i64, i64)],
+        current_time: f64,
+    ) -> Vec<(i64, Vec<u8>)> {
+        for seq in self.base..base {
+            self.handle_ack(seq, current_time);
+        }
+
+        let acked_above: HashSet<i64> = ranges
+            .iter()
+            .flat_map(|&(start, end)| start..end)
+            .collect();
+        for &seq in &acked_above {
+            self.handle_ack(seq, current_time);
+        }
+
+        let horizon = ranges.iter().map(|&(_, end)| end).max().unwrap_or(base);
+        let confirmed_holes: Vec<i64> = (base..horizon)
+            .filter(|seq| !acked_above.contains(seq))
+            .filter(|&seq| self.confirm_hole(seq))
+            .collect();
+
+        confirmed_holes
+            .into_iter()
+            .filter_map(|seq| self.handle_nak(seq, current_time).map(|data| (seq, data)))
+            .collect()
+    }
+
+    /// Counts one more `Sack` report confirming `seq` is still an
+    /// unresolved hole, returning whether this is the `DUP_ACK_THRESHOLD`th
+    /// duplicate report that should be treated as a confirmed loss.
+    ///
+    /// A hole already retransmitted (via this or the timeout path) is
+    /// left alone until it's acked or its own timer fires again, so a
+    /// string of subsequent `Sack`s for the same gap doesn't retransmit
+    /// it, or cut the congestion window, more than once per loss.
+    fn confirm_hole(&mut self, seq: i64) -> bool {
+        if self.retransmitted.contains(&seq) {
+            return false;
+        }
+
+        let reports = self.hole_reports.entry(seq).or_insert(0);
+        *reports += 1;
+
+        if *reports >= DUP_ACK_THRESHOLD {
+            self.hole_reports.remove(&seq);
+            true
+        } else {
+            false
+        }
+    }
 }
 
 /// Link layer receiver state
@@ -88,6 +399,17 @@ pub struct Receiver {
     buffer_size: usize,
     /// Maximum buffer size (256 KB)
     max_buffer_size: usize,
+    /// Sequence numbers buffered above `base`, tracked as merged ranges so
+    /// a gap response can report every received island at once instead of
+    /// just the first one.
+    received: RangeTracker,
+    /// Number of in-order deliveries to coalesce into one cumulative ACK
+    /// before flushing, even if the delayed-ACK timer hasn't fired yet.
+    ack_batch_size: u64,
+    /// In-order deliveries since the last ACK flush, paired with
+    /// `ack_batch_size`; `SimplexLink` flushes this early via
+    /// `flush_pending_ack` if its delayed-ACK timer fires first.
+    pending_acks: u64,
 }
 
 impl Default for Receiver {
@@ -97,18 +419,47 @@ impl Default for Receiver {
             buffer: HashMap::new(),
             buffer_size: 0,
             max_buffer_size: RECEIVER_BUFFER_SIZE,
+            received: RangeTracker::new(),
+            ack_batch_size: 1,
+            pending_acks: 0,
         }
     }
 }
 
 impl Receiver {
-    /// Creates a new Receiver
+    /// Creates a new Receiver that ACKs every in-order frame immediately
+    /// (an `ack_batch_size` of 1).
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Creates a Receiver that coalesces every `ack_batch_size` in-order
+    /// deliveries into one cumulative ACK, relying on `SimplexLink`'s
+    /// delayed-ACK timer (via [`Self::flush_pending_ack`]) to flush a
+    /// partial batch that never reaches the threshold.
+    pub fn with_ack_batch_size(ack_batch_size: u64) -> Self {
+        Self {
+            ack_batch_size: ack_batch_size.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Flushes a cumulative ACK for a batch that hasn't reached
+    /// `ack_batch_size` yet, for the delayed-ACK timer to call once it
+    /// fires. Returns `None` if there's nothing pending.
+    pub fn flush_pending_ack(&mut self) -> Option<Frame> {
+        if self.pending_acks == 0 {
+            return None;
+        }
+
+        self.pending_acks = 0;
+        Some(Frame::Rr(self.base - 1))
+    }
+
     /// Receive a frame and return:
-    /// - Response frame (Rr for ACK, Srej for NAK, None for corrupted)
+    /// - Response frame (Rr for a coalesced/duplicate ACK, Sack for a gap,
+    ///   None for a corrupted frame or an in-order delivery still waiting
+    ///   on its ACK batch)
     /// - List of delivered payloads (in order)
     pub fn receive_frame(&mut self, seq: i64, frame: Frame) -> (Option<Frame>, Vec<Vec<u8>>) {
         match frame {
@@ -128,9 +479,21 @@ impl Receiver {
                         delivered.push(buffered_data);
                         self.base += 1;
                     }
-
-                    // Send ACK
-                    (Some(Frame::Rr(seq)), delivered)
+                    self.received.advance_base(self.base);
+
+                    // Coalesce the ACK: flush a cumulative ACK for the
+                    // highest contiguous sequence once the batch
+                    // threshold is reached, otherwise withhold it and let
+                    // the delayed-ACK timer flush it later.
+                    self.pending_acks += 1;
+                    let response = if self.pending_acks >= self.ack_batch_size {
+                        self.pending_acks = 0;
+                        Some(Frame::Rr(self.base - 1))
+                    } else {
+                        None
+                    };
+
+                    (response, delivered)
                 } else if seq > self.base {
                     // Out-of-order frame - buffer it if space available
                     let data_size = data.len();
@@ -138,24 +501,77 @@ impl Receiver {
                     if self.buffer_size + data_size <= self.max_buffer_size {
                         self.buffer.insert(seq, data);
                         self.buffer_size += data_size;
+                        self.received.insert(seq);
                     }
                     // else: drop frame (buffer full)
 
-                    // Send NAK for missing frame
-                    (Some(Frame::Srej(self.base)), vec![])
+                    // Send a selective ACK describing every received
+                    // island above `base`, not just this one frame.
+                    let ranges = self
+                        .received
+                        .ranges()
+                        .iter()
+                        .take(MAX_SACK_RANGES)
+                        .copied()
+                        .collect();
+                    (
+                        Some(Frame::Sack {
+                            base: self.base,
+                            ranges,
+                        }),
+                        vec![],
+                    )
                 } else {
                     // Duplicate or old frame - just ACK it
                     (Some(Frame::Rr(seq)), vec![])
                 }
             }
             _ => {
-                // Rr/Srej frames shouldn't come here
+                // Rr/Srej/Sack frames shouldn't come here
                 (None, vec![])
             }
         }
     }
 }
 
+/// Arms (or re-arms) `seq`'s retransmission timer. On firing, retransmits
+/// the frame on `channel` and re-arms itself at `fire_time + rto_for(seq)`
+/// (the next exponential-backoff timeout), repeating until
+/// `Sender::handle_ack` clears the frame, at which point
+/// `get_frame_for_timeout` finds nothing left to retransmit and the chain
+/// stops.
+fn retransmit_timer(
+    event_loop: Arc<EventLoop>,
+    sender: Arc<Mutex<Sender>>,
+    channel: Arc<SimplexChannel>,
+    seq: i64,
+    fire_time: f64,
+) -> EventFuture {
+    Box::pin(async move {
+        let Some(data) = sender.lock().await.get_frame_for_timeout(seq, fire_time) else {
+            return;
+        };
+
+        channel.send(fire_time, Frame::Data(data)).await;
+
+        let next_time = fire_time + sender.lock().await.rto_for(seq);
+        let timer_id = event_loop
+            .schedule(
+                retransmit_timer(
+                    event_loop.clone(),
+                    sender.clone(),
+                    channel.clone(),
+                    seq,
+                    next_time,
+                ),
+                next_time,
+            )
+            .await;
+
+        sender.lock().await.timers.insert(seq, timer_id);
+    })
+}
+
 /// Simplex link layer (sender -> receiver)
 pub struct SimplexLink {
     sender: Arc<Mutex<Sender>>,
@@ -165,23 +581,43 @@ pub struct SimplexLink {
     /// Reverse channel (receiver -> sender) for ACK/NAK
     reverse_channel: Arc<SimplexChannel>,
     event_loop: Arc<EventLoop>,
+    /// Delayed-ACK timer: how long a coalesced ACK batch can sit pending
+    /// before it flushes on its own, if `Receiver`'s `ack_batch_size`
+    /// isn't reached first.
+    ack_delay: f64,
+    /// Currently armed delayed-ACK timer, if a batch is pending flush.
+    ack_timer: Arc<Mutex<Option<i64>>>,
 }
 
 impl SimplexLink {
-    /// Creates a new SimplexLink with asymmetric channels
+    /// Creates a new SimplexLink with asymmetric channels, using New Reno
+    /// congestion control. `initial_rto` seeds the adaptive retransmission
+    /// timeout until the SRTT/RTTVAR estimator has a sample to work with.
+    ///
+    /// `ack_batch_size` in-order deliveries are coalesced into one
+    /// cumulative ACK; a batch that never reaches it is flushed anyway
+    /// once `ack_delay` seconds have passed since its first still-pending
+    /// delivery. Out-of-order frames always get an immediate selective
+    /// ACK, so coalescing never delays loss recovery.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         forward_channel: Arc<SimplexChannel>,
         reverse_channel: Arc<SimplexChannel>,
         event_loop: Arc<EventLoop>,
         window_size: i64,
-        timeout: f64,
+        initial_rto: f64,
+        mss: usize,
+        ack_batch_size: u64,
+        ack_delay: f64,
     ) -> Self {
         Self {
-            sender: Arc::new(Mutex::new(Sender::new(window_size, timeout))),
-            receiver: Arc::new(Mutex::new(Receiver::new())),
+            sender: Arc::new(Mutex::new(Sender::new(window_size, initial_rto, mss))),
+            receiver: Arc::new(Mutex::new(Receiver::with_ack_batch_size(ack_batch_size))),
             forward_channel,
             reverse_channel,
             event_loop,
+            ack_delay,
+            ack_timer: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -194,7 +630,7 @@ impl SimplexLink {
             return None; // Window full
         }
 
-        let seq = sender.send_frame(data.clone());
+        let seq = sender.send_frame(data.clone(), current_time);
         debug!(seq, data_len = data.len(), "Sending frame");
 
         // Send through forward channel
@@ -205,23 +641,16 @@ impl SimplexLink {
         let propagation_time =
             frame.size_bits() as f64 / BIT_RATE as f64 + FORWARD_PATH + PROCESSING_DELAY;
 
-        // Schedule timeout event
-        let timeout_time = current_time + propagation_time + sender.timeout;
+        // Schedule timeout event, using the adaptive RTO estimate
+        let timeout_time = current_time + propagation_time + sender.rto_for(seq);
         let event_loop = self.event_loop.clone();
         let sender_clone = self.sender.clone();
         let channel_clone = self.forward_channel.clone(); // Corrected to forward_channel
 
         let timer_id = event_loop
             .schedule(
+                retransmit_timer(event_loop.clone(), sender_clone, channel_clone, seq, timeout_time),
                 timeout_time,
-                Box::pin(async move {
-                    // Timeout handler
-                    let sender = sender_clone.lock().await; // Mutex lock for sender
-                    if let Some(data) = sender.get_frame_for_timeout(seq) {
-                        // Retransmit on forward channel
-                        channel_clone.send(timeout_time, Frame::Data(data)).await;
-                    }
-                }),
             )
             .await;
 
@@ -230,30 +659,79 @@ impl SimplexLink {
         Some(seq)
     }
 
-    /// Receive and process frame at receiver
+    /// Receive and process frame at receiver.
+    ///
+    /// A flush (a coalesced ACK reaching its batch threshold, or an
+    /// immediate selective ACK for a gap) cancels any outstanding
+    /// delayed-ACK timer. An in-order delivery that's withheld pending
+    /// its batch arms one (if none is already running) to flush the
+    /// batch after `ack_delay`, regardless of whether more frames arrive.
     #[instrument(skip(self, frame))]
-    pub async fn receive_frame(&self, seq: i64, frame: Frame) -> (Option<Frame>, Vec<Vec<u8>>) {
-        let mut receiver = self.receiver.lock().await;
-        receiver.receive_frame(seq, frame)
+    pub async fn receive_frame(
+        &self,
+        current_time: f64,
+        seq: i64,
+        frame: Frame,
+    ) -> (Option<Frame>, Vec<Vec<u8>>) {
+        let (response, delivered) = {
+            let mut receiver = self.receiver.lock().await;
+            receiver.receive_frame(seq, frame)
+        };
+
+        match &response {
+            Some(_) => {
+                if let Some(timer_id) = self.ack_timer.lock().await.take() {
+                    self.event_loop.cancel(timer_id).await;
+                }
+            }
+            None if !delivered.is_empty() => {
+                let mut ack_timer = self.ack_timer.lock().await;
+                if ack_timer.is_none() {
+                    let event_loop = self.event_loop.clone();
+                    let receiver = self.receiver.clone();
+                    let reverse_channel = self.reverse_channel.clone();
+                    let ack_timer_slot = self.ack_timer.clone();
+                    let fire_time = current_time + self.ack_delay;
+
+                    let timer_id = event_loop
+                        .schedule(
+                            Box::pin(async move {
+                                *ack_timer_slot.lock().await = None;
+
+                                if let Some(ack) = receiver.lock().await.flush_pending_ack() {
+                                    reverse_channel.send(fire_time, ack).await;
+                                }
+                            }),
+                            fire_time,
+                        )
+                        .await;
+
+                    *ack_timer = Some(timer_id);
+                }
+            }
+            None => {} // corrupted frame - nothing to ack or time out
+        }
+
+        (response, delivered)
     }
 
     /// Handle ACK reception at sender
-    pub async fn handle_ack(&self, seq: i64) {
+    pub async fn handle_ack(&self, current_time: f64, seq: i64) {
         let mut sender = self.sender.lock().await;
 
         // Cancel timer
-        if let Some(timer_id) = sender.timers.get(&seq) {
-            self.event_loop.cancel(*timer_id).await;
+        if let Some(timer_id) = sender.timers.remove(&seq) {
+            self.event_loop.cancel(timer_id).await;
         }
 
-        sender.handle_ack(seq);
+        sender.handle_ack(seq, current_time);
     }
 
     /// Handle NAK reception at sender
     pub async fn handle_nak(&self, current_time: f64, seq: i64) {
-        let sender = self.sender.lock().await;
+        let mut sender = self.sender.lock().await;
 
-        if let Some(data) = sender.handle_nak(seq) {
+        if let Some(data) = sender.handle_nak(seq, current_time) {
             // Retransmit immediately on forward channel
             self.forward_channel
                 .send(current_time, Frame::Data(data))
@@ -261,6 +739,33 @@ impl SimplexLink {
         }
     }
 
+    /// Handle selective ACK reception at sender: cancels the timer of
+    /// every sequence number it acknowledges (cumulatively via `base` or
+    /// selectively via `ranges`), then retransmits the confirmed holes it
+    /// reports.
+    pub async fn handle_sack(&self, current_time: f64, base: i64, ranges: Vec<(i64, i64)>) {
+        let mut sender = self.sender.lock().await;
+
+        let acked: Vec<i64> = (sender.base..base)
+            .chain(ranges.iter().flat_map(|&(start, end)| start..end))
+            .collect();
+
+        for seq in acked {
+            if let Some(timer_id) = sender.timers.remove(&seq) {
+                self.event_loop.cancel(timer_id).await;
+            }
+        }
+
+        let holes = sender.handle_sack(base, &ranges, current_time);
+        drop(sender);
+
+        for (_, data) in holes {
+            self.forward_channel
+                .send(current_time, Frame::Data(data))
+                .await;
+        }
+    }
+
     /// Check if sender can send more
     pub async fn can_send(&self) -> bool {
         self.sender.lock().await.can_send()
@@ -279,6 +784,13 @@ impl SimplexLink {
             .send(current_time, Frame::Srej(seq))
             .await;
     }
+
+    /// Send a selective ACK on reverse channel
+    pub async fn send_sack(&self, current_time: f64, base: i64, ranges: Vec<(i64, i64)>) {
+        self.reverse_channel
+            .send(current_time, Frame::Sack { base, ranges })
+            .await;
+    }
 }
 
 #[cfg(test)]
@@ -291,7 +803,16 @@ mod tests {
         let event_loop = Arc::new(EventLoop::default());
         let forward_channel = Arc::new(SimplexChannel::new(event_loop.clone(), FORWARD_PATH));
         let reverse_channel = Arc::new(SimplexChannel::new(event_loop.clone(), REVERSE_PATH));
-        let link = SimplexLink::new(forward_channel, reverse_channel, event_loop.clone(), 8, 0.1);
+        let link = SimplexLink::new(
+            forward_channel,
+            reverse_channel,
+            event_loop.clone(),
+            8,
+            0.1,
+            512,
+            1,
+            0.04,
+        );
 
         // ========== SECTION 1: In-order delivery ==========
         tracing::info!("Section 1: Testing in-order delivery");
@@ -308,17 +829,17 @@ mod tests {
         event_loop.advance().await;
         event_loop.advance().await;
 
-        let (ack1, delivered1) = link.receive_frame(0, Frame::Data(data1.clone())).await;
+        let (ack1, delivered1) = link.receive_frame(0.01, 0, Frame::Data(data1.clone())).await;
         assert!(matches!(ack1, Some(Frame::Rr(0))), "Should ACK frame 0");
         assert_eq!(delivered1.len(), 1, "Should deliver 1 frame");
         assert_eq!(delivered1[0], data1);
 
-        let (ack2, delivered2) = link.receive_frame(1, Frame::Data(data2.clone())).await;
+        let (ack2, delivered2) = link.receive_frame(0.02, 1, Frame::Data(data2.clone())).await;
         assert!(matches!(ack2, Some(Frame::Rr(1))), "Should ACK frame 1");
         assert_eq!(delivered2.len(), 1);
         assert_eq!(delivered2[0], data2);
 
-        let (ack3, delivered3) = link.receive_frame(2, Frame::Data(data3.clone())).await;
+        let (ack3, delivered3) = link.receive_frame(0.03, 2, Frame::Data(data3.clone())).await;
         assert!(matches!(ack3, Some(Frame::Rr(2))), "Should ACK frame 2");
         assert_eq!(delivered3.len(), 1);
         assert_eq!(delivered3[0], data3);
@@ -331,18 +852,27 @@ mod tests {
         let data12 = vec![12; 100];
 
         // Receive frame 3 (in order after previous section)
-        let (ack10, delivered10) = link.receive_frame(3, Frame::Data(data10.clone())).await;
+        let (ack10, delivered10) = link.receive_frame(0.04, 3, Frame::Data(data10.clone())).await;
         assert!(matches!(ack10, Some(Frame::Rr(3))));
         assert_eq!(delivered10.len(), 1);
 
         // Skip frame 4, receive frame 5 (out of order)
-        let (nak, delivered_nak) = link.receive_frame(5, Frame::Data(data12.clone())).await;
-        assert!(matches!(nak, Some(Frame::Srej(4))), "Should NAK for missing frame 4");
+        let (nak, delivered_nak) = link.receive_frame(0.05, 5, Frame::Data(data12.clone())).await;
+        match nak {
+            Some(Frame::Sack { base, ranges }) => {
+                assert_eq!(base, 4, "Should report 4 as next expected sequence");
+                assert_eq!(ranges, vec![(5, 6)], "Should report frame 5 as received");
+            }
+            other => panic!("Expected a selective ACK, got {:?}", other),
+        }
         assert_eq!(delivered_nak.len(), 0, "Should buffer frame 5, not deliver");
 
         // Now receive missing frame 4
-        let (ack11, delivered11) = link.receive_frame(4, Frame::Data(data11.clone())).await;
-        assert!(matches!(ack11, Some(Frame::Rr(4))));
+        let (ack11, delivered11) = link.receive_frame(0.06, 4, Frame::Data(data11.clone())).await;
+        assert!(
+            matches!(ack11, Some(Frame::Rr(5))),
+            "Should ACK the highest contiguous sequence (5), not just the just-arrived frame 4"
+        );
         assert_eq!(delivered11.len(), 2, "Should deliver frame 4 and buffered frame 5");
         assert_eq!(delivered11[0], data11);
         assert_eq!(delivered11[1], data12);
@@ -351,13 +881,13 @@ mod tests {
         tracing::info!("Section 3: Testing corrupted frame handling");
 
         // Receive corrupted frame
-        let (response, delivered) = link.receive_frame(6, Frame::Corrupted).await;
+        let (response, delivered) = link.receive_frame(0.07, 6, Frame::Corrupted).await;
         assert!(response.is_none(), "Should not respond to corrupted frames");
         assert_eq!(delivered.len(), 0, "Should not deliver corrupted frames");
 
         // Receive valid frame after corrupted one
         let data13 = vec![13; 50];
-        let (ack13, delivered13) = link.receive_frame(6, Frame::Data(data13.clone())).await;
+        let (ack13, delivered13) = link.receive_frame(0.08, 6, Frame::Data(data13.clone())).await;
         assert!(matches!(ack13, Some(Frame::Rr(6))));
         assert_eq!(delivered13.len(), 1);
         assert_eq!(delivered13[0], data13);
@@ -371,7 +901,10 @@ mod tests {
             Arc::new(SimplexChannel::new(event_loop.clone(), REVERSE_PATH)),
             event_loop.clone(),
             2, // Small window
-            0.1
+            0.1,
+            10,
+            1,
+            0.04,
         );
 
         // Fill window
@@ -385,7 +918,7 @@ mod tests {
         assert_eq!(seq2, None, "Should reject when window is full");
 
         // ACK first frame to make room
-        small_link.handle_ack(0).await;
+        small_link.handle_ack(1.003, 0).await;
 
         // Now should be able to send
         let seq2_retry = small_link.send_data(1.003, vec![22; 10]).await;
@@ -402,26 +935,35 @@ mod tests {
             Arc::new(SimplexChannel::new(event_loop.clone(), REVERSE_PATH)),
             event_loop.clone(),
             1000,
-            0.1
+            0.1,
+            512,
+            1,
+            0.04,
         );
 
         let large_data = vec![0u8; 100 * 1024]; // 100KB per frame
 
         // Buffer frames out of order to test overflow
-        let (nak1, _) = overflow_link.receive_frame(1, Frame::Data(large_data.clone())).await;
-        assert!(matches!(nak1, Some(Frame::Srej(0))), "NAK for frame 0");
+        let (nak1, _) = overflow_link.receive_frame(1.01, 1, Frame::Data(large_data.clone())).await;
+        assert!(matches!(nak1, Some(Frame::Sack { base: 0, .. })), "Sack for frame 0");
 
-        let (nak2, _) = overflow_link.receive_frame(2, Frame::Data(large_data.clone())).await;
-        assert!(matches!(nak2, Some(Frame::Srej(0))), "NAK for frame 0");
+        let (nak2, _) = overflow_link.receive_frame(1.02, 2, Frame::Data(large_data.clone())).await;
+        assert!(matches!(nak2, Some(Frame::Sack { base: 0, .. })), "Sack for frame 0");
 
         // Third frame should be dropped (100 + 100 + 100 > 256KB)
-        let (nak3, delivered3) = overflow_link.receive_frame(3, Frame::Data(large_data.clone())).await;
-        assert!(matches!(nak3, Some(Frame::Srej(0))), "Still NAK even when dropping");
+        let (nak3, delivered3) = overflow_link.receive_frame(1.03, 3, Frame::Data(large_data.clone())).await;
+        assert!(
+            matches!(nak3, Some(Frame::Sack { base: 0, .. })),
+            "Still Sack even when dropping"
+        );
         assert_eq!(delivered3.len(), 0);
 
         // Send missing frame 0
-        let (ack0, delivered0) = overflow_link.receive_frame(0, Frame::Data(vec![0; 10])).await;
-        assert!(matches!(ack0, Some(Frame::Rr(0))));
+        let (ack0, delivered0) = overflow_link.receive_frame(1.04, 0, Frame::Data(vec![0; 10])).await;
+        assert!(
+            matches!(ack0, Some(Frame::Rr(2))),
+            "Should ACK the highest contiguous sequence (2) delivered by this batch"
+        );
         // Should deliver 0, 1, 2 but NOT 3 (was dropped)
         assert_eq!(delivered0.len(), 3, "Should deliver buffered frames, but not the dropped one");
 
@@ -431,13 +973,145 @@ mod tests {
         let dup_data = vec![30; 50];
 
         // Send and receive frame 7
-        let (ack_first, delivered_first) = link.receive_frame(7, Frame::Data(dup_data.clone())).await;
+        let (ack_first, delivered_first) = link.receive_frame(0.09, 7, Frame::Data(dup_data.clone())).await;
         assert!(matches!(ack_first, Some(Frame::Rr(7))));
         assert_eq!(delivered_first.len(), 1);
 
         // Receive duplicate of frame 7
-        let (ack_dup, delivered_dup) = link.receive_frame(7, Frame::Data(dup_data.clone())).await;
+        let (ack_dup, delivered_dup) = link.receive_frame(0.10, 7, Frame::Data(dup_data.clone())).await;
         assert!(matches!(ack_dup, Some(Frame::Rr(7))), "Should ACK duplicate");
         assert_eq!(delivered_dup.len(), 0, "Should not deliver duplicate");
     }
+
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_selective_ack_multiple_gaps() {
+        // Receiver side: multiple disjoint gaps reported in one Sack.
+        let mut receiver = Receiver::new();
+        let (sack1, _) = receiver.receive_frame(2, Frame::Data(vec![2; 10]));
+        assert!(matches!(sack1, Some(Frame::Sack { base: 0, .. })));
+
+        let (sack2, delivered) = receiver.receive_frame(5, Frame::Data(vec![5; 10]));
+        let (base, ranges) = match sack2 {
+            Some(Frame::Sack { base, ranges }) => (base, ranges),
+            other => panic!("Expected a selective ACK, got {:?}", other),
+        };
+        assert_eq!(base, 0, "Nothing in-order has arrived yet");
+        assert_eq!(delivered.len(), 0);
+        assert_eq!(
+            ranges,
+            vec![(2, 3), (5, 6)],
+            "Should report both disjoint islands in one selective ACK"
+        );
+
+        // Sender side: feeding that same selective ACK back clears the
+        // acknowledged frames in one pass, but only reports the true
+        // holes for retransmission once they've been confirmed by
+        // `DUP_ACK_THRESHOLD` successive selective ACKs.
+        let mut sender = Sender::new(8, 0.1, 10);
+        for seq in 0..8i64 {
+            sender.send_frame(vec![seq as u8; 10], 0.0);
+        }
+
+        let holes = sender.handle_sack(base, &ranges, 0.2);
+        assert!(
+            holes.is_empty(),
+            "A single selective ACK shouldn't fast-retransmit yet"
+        );
+
+        let holes = sender.handle_sack(base, &ranges, 0.2);
+        assert!(
+            holes.is_empty(),
+            "Two selective ACKs still shouldn't fast-retransmit"
+        );
+
+        let holes = sender.handle_sack(base, &ranges, 0.2);
+        let mut hole_seqs: Vec<i64> = holes.iter().map(|&(seq, _)| seq).collect();
+        hole_seqs.sort();
+        assert_eq!(
+            hole_seqs,
+            vec![0, 1, 3, 4],
+            "The third successive selective ACK should confirm only the \
+             holes below the top of the ranges"
+        );
+
+        // The acknowledged sequence numbers should be gone from the
+        // sender's outstanding set.
+        assert!(sender.sent_frames.get(&2).is_none());
+        assert!(sender.sent_frames.get(&5).is_none());
+
+        // A further selective ACK for the same gaps shouldn't retransmit
+        // again until they're acked or time out.
+        let holes = sender.handle_sack(base, &ranges, 0.2);
+        assert!(
+            holes.is_empty(),
+            "An already-retransmitted hole shouldn't fast-retransmit again"
+        );
+    }
+
+    #[test]
+    fn test_frame_check_clean_below_ber_threshold() {
+        let mut check = FrameCheck::new_seeded_crc16(1);
+
+        // Zero BER can never flip a bit, so every outcome must be clean.
+        for _ in 0..10 {
+            assert_eq!(check.frame_outcome(1000, 0.0), FrameOutcome::Clean);
+        }
+    }
+
+    #[test]
+    fn test_frame_check_certain_corruption_is_detected_or_undetected() {
+        let mut check = FrameCheck::new_seeded_crc16(2);
+
+        // BER of 1.0 guarantees at least one flipped bit, so the outcome
+        // must be one of the two corruption variants, never Clean.
+        let outcomes: Vec<FrameOutcome> =
+            (0..200).map(|_| check.frame_outcome(10, 1.0)).collect();
+
+        assert!(outcomes.iter().all(|&o| o != FrameOutcome::Clean));
+        assert!(
+            outcomes.contains(&FrameOutcome::DetectedError),
+            "a 16-bit CRC should catch the overwhelming majority of corrupted frames"
+        );
+    }
+
+    #[test]
+    fn test_ack_batching_and_delayed_flush() {
+        let mut receiver = Receiver::with_ack_batch_size(3);
+
+        // The first two in-order deliveries stay below the batch
+        // threshold, so the ACK is withheld despite the data being
+        // delivered.
+        let (ack1, delivered1) = receiver.receive_frame(0, Frame::Data(vec![0; 10]));
+        assert!(ack1.is_none(), "Should withhold the ACK below the batch threshold");
+        assert_eq!(delivered1, vec![vec![0; 10]]);
+
+        let (ack2, delivered2) = receiver.receive_frame(1, Frame::Data(vec![1; 10]));
+        assert!(ack2.is_none());
+        assert_eq!(delivered2, vec![vec![1; 10]]);
+
+        // The third delivery reaches the threshold and flushes a
+        // cumulative ACK for the highest contiguous sequence seen so far.
+        let (ack3, delivered3) = receiver.receive_frame(2, Frame::Data(vec![2; 10]));
+        assert!(matches!(ack3, Some(Frame::Rr(2))));
+        assert_eq!(delivered3, vec![vec![2; 10]]);
+
+        // A fourth, below-threshold delivery leaves an ACK pending...
+        let (ack4, _) = receiver.receive_frame(3, Frame::Data(vec![3; 10]));
+        assert!(ack4.is_none());
+
+        // ...which the delayed-ACK timer flushes on firing, still
+        // carrying the highest contiguous sequence.
+        let flushed = receiver.flush_pending_ack();
+        assert!(matches!(flushed, Some(Frame::Rr(3))));
+
+        // Flushing again with nothing pending (e.g. a stale timer firing
+        // after the batch already flushed) is a no-op.
+        assert!(receiver.flush_pending_ack().is_none());
+
+        // Out-of-order frames always get an immediate selective ACK,
+        // regardless of the batch threshold.
+        let (sack, _) = receiver.receive_frame(6, Frame::Data(vec![6; 10]));
+        assert!(matches!(sack, Some(Frame::Sack { base: 4, .. })));
+    }
 }
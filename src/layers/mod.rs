@@ -4,5 +4,5 @@ pub mod physical;
 /// Link layer definitions
 pub mod link;
 
-/// Transport layer definitions
-pub mod transport;
+/// Merged-range tracking for selective-ACK reporting, used by [`link`].
+pub mod range_tracker;
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{common::*, event_loop::EventLoop};
 
-use rand::random;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use tokio::{
     sync::{
         Mutex,
@@ -12,6 +12,10 @@ use tokio::{
 };
 use tracing::{debug, instrument};
 
+/// Bits used to encode each `(start, end)` range in a [`Frame::Sack`], two
+/// 32-bit sequence-number fields.
+const SACK_RANGE_BITS: u64 = 64;
+
 /// Pyhsical layer frame
 #[derive(Clone, Debug)]
 pub enum Frame {
@@ -19,6 +23,15 @@ pub enum Frame {
     Rr(i64),
     /// Negative ACK
     Srej(i64),
+    /// Selective ACK: cumulative ack point (`base`, the next expected
+    /// in-order sequence number) plus a bounded list of disjoint
+    /// `[start, end)` ranges of sequence numbers received above it.
+    Sack {
+        /// Next expected in-order sequence number.
+        base: i64,
+        /// Disjoint, sorted `[start, end)` ranges received above `base`.
+        ranges: Vec<(i64, i64)>,
+    },
     /// Underlying data array
     Data(Vec<u8>),
     /// Unit type represent a corrupted frame. Ignored by the rx.
@@ -31,6 +44,7 @@ impl Frame {
         match self {
             Self::Rr(_) => FRAME_OVERHEAD,
             Self::Srej(_) => FRAME_OVERHEAD,
+            Self::Sack { ranges, .. } => FRAME_OVERHEAD + ranges.len() as u64 * SACK_RANGE_BITS,
             Self::Data(data) => data.len() as u64 * 8 + FRAME_OVERHEAD,
             _ => unreachable!("unexcepted send of a corrupted frame"),
         }
@@ -44,11 +58,35 @@ pub struct SimplexChannel {
     event_loop: Arc<EventLoop>,
     propagation_delay: f64,
     is_good: Mutex<bool>,
+    rng: Mutex<StdRng>,
+    params: ChannelParams,
 }
 
 impl SimplexChannel {
-    /// Creates a new simplex channel.
+    /// Creates a new simplex channel, seeded from OS entropy.
+    ///
+    /// Runs built on this constructor are not reproducible; use
+    /// [`Self::new_seeded`] to bisect a specific run.
     pub fn new(event_loop: Arc<EventLoop>, propagation_delay: f64) -> Self {
+        Self::new_seeded(event_loop, propagation_delay, rand::rng().random())
+    }
+
+    /// Creates a new simplex channel with a fixed seed, so the resulting
+    /// corruption pattern and state transitions are bit-for-bit
+    /// reproducible across runs.
+    pub fn new_seeded(event_loop: Arc<EventLoop>, propagation_delay: f64, seed: u64) -> Self {
+        Self::new_seeded_with_params(event_loop, propagation_delay, seed, ChannelParams::default())
+    }
+
+    /// Creates a new simplex channel with a fixed seed and explicit
+    /// channel-model parameters, so a BER or transition-probability sweep
+    /// doesn't require recompiling.
+    pub fn new_seeded_with_params(
+        event_loop: Arc<EventLoop>,
+        propagation_delay: f64,
+        seed: u64,
+        params: ChannelParams,
+    ) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
 
         Self {
@@ -57,6 +95,8 @@ impl SimplexChannel {
             event_loop,
             propagation_delay,
             is_good: Mutex::new(true),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            params,
         }
     }
 
@@ -64,48 +104,34 @@ impl SimplexChannel {
     #[instrument(skip(self, frame))]
     pub async fn send(&self, time: f64, frame: Frame) -> (f64, f64) {
         let mut is_good = self.is_good.lock().await;
+        let mut rng = self.rng.lock().await;
         let corrupted;
 
-        (corrupted, *is_good) = task::spawn_blocking({
-            let mut next_state = *is_good;
+        (corrupted, *is_good, *rng) = task::spawn_blocking({
+            let next_state = *is_good;
             let size_bits = frame.size_bits();
+            let mut rng = rng.clone();
+            let params = self.params;
 
             move || {
-                let mut corrupted = false;
-
-                for _ in 0..size_bits {
-                    let r: f64 = random();
-
-                    if next_state {
-                        if r < GOOD_STATE_BER {
-                            corrupted = true;
-                        }
-                        if r < P_G_TO_B {
-                            next_state = false;
-                        }
-                    } else {
-                        if r < BAD_STATE_BER {
-                            corrupted = true;
-                        }
-                        if r < P_B_TO_G {
-                            next_state = true;
-                        }
-                    }
-                }
+                let (corrupted, next_state) = if USE_EXACT_BER_LOOP {
+                    Self::exact_ber_loop(next_state, size_bits, &mut rng, &params)
+                } else {
+                    Self::geometric_ber_skip(next_state, size_bits, &mut rng, &params)
+                };
 
-                (corrupted, next_state)
+                (corrupted, next_state, rng)
             }
         })
         .await
         .unwrap();
 
-        let propagation_duration: f64 = frame.size_bits() as f64 / BIT_RATE as f64;
+        let propagation_duration: f64 = frame.size_bits() as f64 / self.params.bit_rate as f64;
 
-        let rtt = propagation_duration + self.propagation_delay + PROCESSING_DELAY;
+        let rtt = propagation_duration + self.propagation_delay + self.params.processing_delay;
 
         self.event_loop
             .schedule(
-                time + rtt,
                 Box::pin({
                     let tx = self.tx.clone();
 
@@ -114,6 +140,7 @@ impl SimplexChannel {
                             .ok();
                     }
                 }),
+                time + rtt,
             )
             .await;
         debug!(propagation_duration, rtt, corrupted, "Schedule for send");
@@ -121,6 +148,100 @@ impl SimplexChannel {
         (propagation_duration, rtt)
     }
 
+    /// Exact per-bit Gilbert-Elliot sampler.
+    ///
+    /// Draws one uniform per bit and couples the BER test and the state
+    /// transition test to the same draw. Kept only to validate
+    /// [`Self::geometric_ber_skip`] against; O(bits) draws makes it
+    /// unsuitable for large frames.
+    fn exact_ber_loop(
+        state: bool,
+        size_bits: u64,
+        rng: &mut StdRng,
+        params: &ChannelParams,
+    ) -> (bool, bool) {
+        let mut next_state = state;
+        let mut corrupted = false;
+
+        for _ in 0..size_bits {
+            let r: f64 = rng.random();
+
+            if next_state {
+                if r < params.good_state_ber {
+                    corrupted = true;
+                }
+                if r < params.p_g_to_b {
+                    next_state = false;
+                }
+            } else {
+                if r < params.bad_state_ber {
+                    corrupted = true;
+                }
+                if r < params.p_b_to_g {
+                    next_state = true;
+                }
+            }
+        }
+
+        (corrupted, next_state)
+    }
+
+    /// Geometric run-length Gilbert-Elliot sampler.
+    ///
+    /// Exploits the fact that only two outputs are needed (whether *any*
+    /// bit errored and the ending state) by sampling the dwell length
+    /// until the next state transition as a geometric random variable,
+    /// then drawing a single uniform to decide whether the frame was
+    /// corrupted over that whole run. This turns O(bits) draws into
+    /// O(state-transitions) draws, at the cost of decoupling the BER and
+    /// transition tests, so it is only statistically equivalent to
+    /// [`Self::exact_ber_loop`] in the limit of small probabilities.
+    fn geometric_ber_skip(
+        state: bool,
+        size_bits: u64,
+        rng: &mut StdRng,
+        params: &ChannelParams,
+    ) -> (bool, bool) {
+        let mut next_state = state;
+        let mut corrupted = false;
+        let mut bits_remaining = size_bits as i64;
+
+        while bits_remaining > 0 {
+            let (ber, transition_p) = if next_state {
+                (params.good_state_ber, params.p_g_to_b)
+            } else {
+                (params.bad_state_ber, params.p_b_to_g)
+            };
+
+            // A state with `transition_p == 0` never leaves, so its dwell
+            // is clamped to `i64::MAX` rather than dividing by `ln(1) == 0`.
+            let full_dwell = if transition_p <= 0.0 {
+                i64::MAX
+            } else {
+                let u: f64 = rng.random();
+                (u.ln() / (1.0 - transition_p).ln()).floor() as i64 + 1
+            };
+            let dwell = full_dwell.min(bits_remaining);
+
+            if !corrupted {
+                let r: f64 = rng.random();
+                if r < 1.0 - (1.0 - ber).powf(dwell as f64) {
+                    corrupted = true;
+                }
+            }
+
+            bits_remaining -= dwell;
+
+            // Only transition once the sampled dwell has fully elapsed;
+            // if the frame ended first, the state carries over unchanged.
+            if dwell == full_dwell {
+                next_state = !next_state;
+            }
+        }
+
+        (corrupted, next_state)
+    }
+
     /// Receives the next frame
     #[instrument(skip(self))]
     pub async fn receive(&self) -> (f64, Frame) {
@@ -154,7 +275,7 @@ mod tests {
     #[test_log::test]
     async fn test_channel_send_receive() {
         let event_loop = Arc::new(EventLoop::default());
-        let channel = SimplexChannel::new(event_loop.clone(), FORWARD_PATH);
+        let channel = SimplexChannel::new_seeded(event_loop.clone(), FORWARD_PATH, 1);
 
         // Send a frame
         let data = Frame::Data(vec![1, 2, 3]);
@@ -174,10 +295,10 @@ mod tests {
             (3 * 8 + FRAME_OVERHEAD) as f64 / BIT_RATE as f64 + FORWARD_PATH + PROCESSING_DELAY;
         assert!((recv_time - expected_time).abs() < 1e-6);
 
-        // Check frame (might be corrupted or data)
+        // With a fixed seed and a 216-bit frame at the good-state BER, the
+        // frame is deterministically delivered uncorrupted.
         match recv_frame {
             Frame::Data(d) => assert_eq!(d, vec![1, 2, 3]),
-            Frame::Corrupted => {} // Can happen randomly
             _ => panic!("Unexpected frame type"),
         }
     }
@@ -253,11 +374,28 @@ mod tests {
         assert!(time2 > time1);
     }
 
+    #[tokio::test]
+    #[test_log::test]
+    async fn test_geometric_ber_skip_handles_zero_transition_probability() {
+        // A pure single-state channel (no transitions out of the good
+        // state) must not panic: the dwell has to clamp to the frame
+        // size rather than divide by ln(1) == 0.
+        let mut rng = StdRng::seed_from_u64(1);
+        let params = ChannelParams::default()
+            .with_p_g_to_b(0.0)
+            .with_p_b_to_g(0.0);
+
+        let (_, next_state) =
+            SimplexChannel::geometric_ber_skip(true, 1000, &mut rng, &params);
+
+        assert!(next_state);
+    }
+
     #[tokio::test]
     #[test_log::test]
     async fn test_ack_nak_frames() {
         let event_loop = Arc::new(EventLoop::default());
-        let channel = SimplexChannel::new(event_loop.clone(), REVERSE_PATH);
+        let channel = SimplexChannel::new_seeded(event_loop.clone(), REVERSE_PATH, 1);
 
         // Send ACK
         channel.send(0.0, Frame::Rr(42)).await;
@@ -266,7 +404,6 @@ mod tests {
         let (_, frame) = channel.receive().await;
         match frame {
             Frame::Rr(seq) => assert_eq!(seq, 42),
-            Frame::Corrupted => {} // Can happen
             _ => panic!("Expected Rr frame"),
         }
     }
@@ -0,0 +1,113 @@
+/// Tracks a set of received sequence numbers as a sorted list of disjoint,
+/// merged `[start, end)` ranges, mirroring the SACK option's range
+/// encoding. Used by [`super::link::Receiver`] to report every received
+/// island in a single selective-ACK frame instead of one gap at a time.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RangeTracker {
+    ranges: Vec<(i64, i64)>,
+}
+
+impl RangeTracker {
+    /// Creates an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether `seq` falls inside one of the tracked ranges.
+    pub fn contains(&self, seq: i64) -> bool {
+        let idx = self.ranges.partition_point(|&(start, _)| start <= seq);
+        idx > 0 && self.ranges[idx - 1].1 > seq
+    }
+
+    /// Records `seq` as received, merging it into an adjacent/overlapping
+    /// range or inserting a new singleton range, keeping `ranges` sorted
+    /// and disjoint.
+    pub fn insert(&mut self, seq: i64) {
+        if self.contains(seq) {
+            return;
+        }
+
+        let idx = self.ranges.partition_point(|&(start, _)| start <= seq);
+        let merge_left = idx > 0 && self.ranges[idx - 1].1 == seq;
+        let merge_right = idx < self.ranges.len() && self.ranges[idx].0 == seq + 1;
+
+        match (merge_left, merge_right) {
+            (true, true) => {
+                let (_, right_end) = self.ranges.remove(idx);
+                self.ranges[idx - 1].1 = right_end;
+            }
+            (true, false) => self.ranges[idx - 1].1 = seq + 1,
+            (false, true) => self.ranges[idx].0 = seq,
+            (false, false) => self.ranges.insert(idx, (seq, seq + 1)),
+        }
+    }
+
+    /// Drops every sequence number below `base`, since it's now implied by
+    /// the cumulative ack point and no longer needs reporting selectively.
+    pub fn advance_base(&mut self, base: i64) {
+        self.ranges.retain(|&(_, end)| end > base);
+
+        if let Some(first) = self.ranges.first_mut()
+            && first.0 < base
+        {
+            first.0 = base;
+        }
+    }
+
+    /// The tracked ranges, sorted ascending and non-overlapping.
+    pub fn ranges(&self) -> &[(i64, i64)] {
+        &self.ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_adjacent_and_overlapping_inserts() {
+        let mut tracker = RangeTracker::new();
+        tracker.insert(5);
+        tracker.insert(6);
+        tracker.insert(4);
+        assert_eq!(tracker.ranges(), &[(4, 7)]);
+
+        tracker.insert(10);
+        assert_eq!(tracker.ranges(), &[(4, 7), (10, 11)]);
+
+        // Bridges the two existing ranges into one.
+        tracker.insert(7);
+        tracker.insert(8);
+        tracker.insert(9);
+        assert_eq!(tracker.ranges(), &[(4, 11)]);
+    }
+
+    #[test]
+    fn tracks_disjoint_gaps() {
+        let mut tracker = RangeTracker::new();
+        tracker.insert(2);
+        tracker.insert(7);
+        tracker.insert(8);
+
+        assert_eq!(tracker.ranges(), &[(2, 3), (7, 9)]);
+        assert!(tracker.contains(7));
+        assert!(!tracker.contains(3));
+    }
+
+    #[test]
+    fn advance_base_drops_and_clips_ranges() {
+        let mut tracker = RangeTracker::new();
+        tracker.insert(2);
+        tracker.insert(5);
+        tracker.insert(6);
+
+        tracker.advance_base(3);
+        assert_eq!(tracker.ranges(), &[(5, 7)]);
+
+        tracker.advance_base(6);
+        assert_eq!(tracker.ranges(), &[(6, 7)]);
+
+        tracker.advance_base(7);
+        assert!(tracker.ranges().is_empty());
+    }
+}
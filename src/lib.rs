@@ -9,5 +9,16 @@ pub mod common;
 /// Network layers.
 pub mod layers;
 
+/// Pluggable congestion-control strategies consulted by [`layers::link::Sender`].
+pub mod congestion;
+
 /// Frame/timer scheduler.
 pub mod event_loop;
+
+/// Channel corruption models used by [`simulation::simulate_arq`].
+pub mod channel;
+pub use channel::{ChannelStrategy, MarkovChannel};
+
+/// Selective-repeat ARQ simulation entry point.
+pub mod simulation;
+pub use simulation::{SimulationStats, simulate_arq};
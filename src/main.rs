@@ -1,9 +1,12 @@
+use arq_sim::channel::ChannelStrategy;
+use arq_sim::common::{ChannelParams, LinkParams};
+use arq_sim::congestion::CongestionStrategy;
 use arq_sim::{simulate_arq, SimulationStats};
 use dotenvy::dotenv;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // CLI argument parsing
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 // Parallel execution
@@ -34,6 +37,31 @@ enum Commands {
         /// Frame payload size in bytes
         #[arg(short = 'l', long)]
         frame_payload: u64,
+
+        /// Congestion-window strategy capping `window_size`
+        #[arg(long, value_enum, default_value_t = Congestion::Fixed)]
+        congestion: Congestion,
+
+        /// Number of data frames whose acknowledgments are coalesced into
+        /// a single SACK-style reverse-channel transmission
+        #[arg(long, default_value = "4")]
+        ack_batch_size: u64,
+
+        /// Delayed-ACK timer, in seconds: a coalesced batch also flushes
+        /// once this much time has passed since its first frame, even if
+        /// `ack_batch_size` hasn't been reached yet
+        #[arg(long, default_value = "0.04")]
+        ack_delay: f64,
+
+        /// Channel corruption model
+        #[arg(long, value_enum, default_value_t = Channel::GilbertElliot)]
+        channel: Channel,
+
+        /// RNG seed for the forward/reverse channel corruption pattern;
+        /// rerun a surprising `Search` outlier with its recorded seed to
+        /// reproduce and bisect it
+        #[arg(long, default_value_t = DEFAULT_SEED)]
+        seed: u64,
     },
 
     /// Run parameter space search
@@ -54,18 +82,102 @@ enum Commands {
         #[arg(long)]
         parallel: bool,
 
+        /// Congestion-window strategy capping each window size
+        #[arg(long, value_enum, default_value_t = Congestion::Fixed)]
+        congestion: Congestion,
+
+        /// Number of data frames whose acknowledgments are coalesced into
+        /// a single SACK-style reverse-channel transmission
+        #[arg(long, default_value = "4")]
+        ack_batch_size: u64,
+
+        /// Delayed-ACK timer, in seconds: a coalesced batch also flushes
+        /// once this much time has passed since its first frame, even if
+        /// `ack_batch_size` hasn't been reached yet
+        #[arg(long, default_value = "0.04")]
+        ack_delay: f64,
+
+        /// Channel corruption model
+        #[arg(long, value_enum, default_value_t = Channel::GilbertElliot)]
+        channel: Channel,
+
         /// Output CSV file path
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
 }
 
+/// CLI-facing mirror of [`CongestionStrategy`]; `clap::ValueEnum` can't be
+/// derived directly on a type from another crate.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Congestion {
+    /// No dynamic congestion window; see [`arq_sim::congestion::FixedWindow`].
+    Fixed,
+    /// TCP New Reno; see [`arq_sim::congestion::NewReno`].
+    NewReno,
+    /// CUBIC; see [`arq_sim::congestion::Cubic`].
+    Cubic,
+}
+
+impl From<Congestion> for CongestionStrategy {
+    fn from(congestion: Congestion) -> Self {
+        match congestion {
+            Congestion::Fixed => Self::Fixed,
+            Congestion::NewReno => Self::NewReno,
+            Congestion::Cubic => Self::Cubic,
+        }
+    }
+}
+
+impl std::fmt::Display for Congestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed => write!(f, "fixed"),
+            Self::NewReno => write!(f, "new-reno"),
+            Self::Cubic => write!(f, "cubic"),
+        }
+    }
+}
+
+/// CLI-facing mirror of [`ChannelStrategy`]; `clap::ValueEnum` can't be
+/// derived directly on a type from another crate.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Channel {
+    /// Correlated good/bad burst-loss model; see
+    /// [`arq_sim::MarkovChannel::gilbert_elliott`].
+    GilbertElliot,
+    /// Independent, memoryless per-frame corruption; see
+    /// [`arq_sim::channel::BernoulliChannel`].
+    Bernoulli,
+}
+
+impl From<Channel> for ChannelStrategy {
+    fn from(channel: Channel) -> Self {
+        match channel {
+            Channel::GilbertElliot => Self::GilbertElliot,
+            Channel::Bernoulli => Self::Bernoulli,
+        }
+    }
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GilbertElliot => write!(f, "gilbert-elliot"),
+            Self::Bernoulli => write!(f, "bernoulli"),
+        }
+    }
+}
+
 /// Default window sizes from HW2 specification
 const DEFAULT_WINDOW_SIZES: &[u64] = &[2, 4, 8, 16, 32, 64];
 
 /// Default frame payload sizes from HW2 specification
 const DEFAULT_FRAME_PAYLOADS: &[u64] = &[128, 256, 512, 1024, 2048, 4096];
 
+/// Seed used for `Single` runs, for reproducibility across invocations
+const DEFAULT_SEED: u64 = 42;
+
 fn main() {
     dotenv().ok();
 
@@ -83,47 +195,139 @@ fn main() {
         Some(Commands::Single {
             window_size,
             frame_payload,
+            congestion,
+            ack_batch_size,
+            ack_delay,
+            channel,
+            seed,
         }) => {
-            run_single_simulation(window_size, frame_payload);
+            run_single_simulation(
+                window_size,
+                frame_payload,
+                congestion,
+                ack_batch_size,
+                ack_delay,
+                channel,
+                seed,
+            );
         }
         Some(Commands::Search {
             window_sizes,
             frame_payloads,
             num_runs,
             parallel,
+            congestion,
+            ack_batch_size,
+            ack_delay,
+            channel,
             output,
         }) => {
-            run_parameter_search(window_sizes, frame_payloads, num_runs, parallel, output);
+            run_parameter_search(SearchConfig {
+                window_sizes,
+                frame_payloads,
+                num_runs,
+                parallel,
+                congestion,
+                ack_batch_size,
+                ack_delay,
+                channel,
+                output,
+            });
         }
         None => {
             // Default behavior: run single simulation
             println!("Running default simulation (W=2048, L=256)...");
-            run_single_simulation(2048, 256);
+            run_single_simulation(
+                2048,
+                256,
+                Congestion::Fixed,
+                4,
+                0.04,
+                Channel::GilbertElliot,
+                DEFAULT_SEED,
+            );
         }
     }
 }
 
-fn run_single_simulation(window_size: u64, frame_payload: u64) {
+fn run_single_simulation(
+    window_size: u64,
+    frame_payload: u64,
+    congestion: Congestion,
+    ack_batch_size: u64,
+    ack_delay: f64,
+    channel: Channel,
+    seed: u64,
+) {
     println!("Running simulation:");
     println!("  Window size: {}", window_size);
     println!("  Frame payload: {} bytes", frame_payload);
+    println!("  Congestion control: {}", congestion);
+    println!("  ACK batch size: {}", ack_batch_size);
+    println!("  ACK delay: {:.3} s", ack_delay);
+    println!("  Channel model: {}", channel);
+    println!("  Seed: {}", seed);
     println!();
 
-    let stats = simulate_arq(window_size, frame_payload);
+    let stats = simulate_arq(
+        window_size,
+        frame_payload,
+        seed,
+        congestion.into(),
+        ack_batch_size,
+        ack_delay,
+        ChannelParams::default(),
+        LinkParams::default(),
+        channel.into(),
+    );
 
     println!("Results:");
     println!("  Goodput: {:.6} Mbps", stats.goodput / 1_000_000.0);
     println!("  Retransmissions: {}", stats.retransmissions);
+    println!(
+        "    (timeout-triggered: {}, NACK-triggered: {})",
+        stats.timeout_retransmissions, stats.nack_retransmissions
+    );
     println!("  Time: {:.3} s", stats.time);
+    println!(
+        "  Steady-state timeout: {:.6} s",
+        stats.steady_state_timeout
+    );
+    println!(
+        "  Window size: avg {:.2}, min {}",
+        stats.avg_window_size, stats.min_window_size
+    );
+    println!("  Reverse-channel ACK frames: {}", stats.reverse_ack_frames);
+    println!("  Undetected (residual) errors: {}", stats.undetected_errors);
 }
 
-fn run_parameter_search(
+/// Parameters for a [`Commands::Search`] sweep, bundled so the run
+/// function doesn't have to take each CLI flag as its own argument.
+struct SearchConfig {
     window_sizes: Option<Vec<u64>>,
     frame_payloads: Option<Vec<u64>>,
     num_runs: usize,
     parallel: bool,
+    congestion: Congestion,
+    ack_batch_size: u64,
+    ack_delay: f64,
+    channel: Channel,
     output: Option<PathBuf>,
-) {
+}
+
+fn run_parameter_search(config: SearchConfig) {
+    let SearchConfig {
+        window_sizes,
+        frame_payloads,
+        num_runs,
+        parallel,
+        congestion,
+        ack_batch_size,
+        ack_delay,
+        channel,
+        output,
+    } = config;
+
     // Use defaults if not specified
     let window_sizes = window_sizes.unwrap_or_else(|| DEFAULT_WINDOW_SIZES.to_vec());
     let frame_payloads = frame_payloads.unwrap_or_else(|| DEFAULT_FRAME_PAYLOADS.to_vec());
@@ -135,7 +339,9 @@ fn run_parameter_search(
     println!("  Parallel: {}", parallel);
     println!();
 
-    // Generate all combinations
+    // Generate all combinations. `run` doubles as the per-run seed, so
+    // reproducing a specific (W, L, run) outlier from a `--parallel` sweep
+    // is just `Single --seed <run>`.
     let mut params: Vec<(u64, u64, usize)> = Vec::new();
     for &w in &window_sizes {
         for &l in &frame_payloads {
@@ -159,22 +365,44 @@ fn run_parameter_search(
     );
 
     // Run simulations
-    let results: Vec<(u64, u64, usize, SimulationStats)> = if parallel {
+    let results: Vec<(u64, u64, usize, u64, SimulationStats)> = if parallel {
         params
             .par_iter()
             .map(|&(w, l, run)| {
-                let stats = simulate_arq(w, l);
+                let seed = run as u64;
+                let stats = simulate_arq(
+                    w,
+                    l,
+                    seed,
+                    congestion.into(),
+                    ack_batch_size,
+                    ack_delay,
+                    ChannelParams::default(),
+                    LinkParams::default(),
+                    channel.into(),
+                );
                 pb.inc(1);
-                (w, l, run, stats)
+                (w, l, run, seed, stats)
             })
             .collect()
     } else {
         params
             .iter()
             .map(|&(w, l, run)| {
-                let stats = simulate_arq(w, l);
+                let seed = run as u64;
+                let stats = simulate_arq(
+                    w,
+                    l,
+                    seed,
+                    congestion.into(),
+                    ack_batch_size,
+                    ack_delay,
+                    ChannelParams::default(),
+                    LinkParams::default(),
+                    channel.into(),
+                );
                 pb.inc(1);
-                (w, l, run, stats)
+                (w, l, run, seed, stats)
             })
             .collect()
     };
@@ -186,7 +414,7 @@ fn run_parameter_search(
     let mut avg_goodput: std::collections::HashMap<(u64, u64), Vec<f64>> =
         std::collections::HashMap::new();
 
-    for (w, l, _run, stats) in &results {
+    for (w, l, _run, _seed, stats) in &results {
         avg_goodput
             .entry((*w, *l))
             .or_default()
@@ -222,27 +450,34 @@ fn run_parameter_search(
     }
 }
 
-fn export_to_csv(results: &[(u64, u64, usize, SimulationStats)], path: &PathBuf) {
+fn export_to_csv(results: &[(u64, u64, usize, u64, SimulationStats)], path: &PathBuf) {
     let mut file = File::create(path).expect("Failed to create CSV file");
 
     // Write header
     writeln!(
         file,
-        "window_size,frame_payload,run,goodput_mbps,retransmissions,time_seconds"
+        "window_size,frame_payload,run,seed,goodput_mbps,retransmissions,timeout_retransmissions,nack_retransmissions,time_seconds,avg_window_size,min_window_size,reverse_ack_frames,undetected_errors"
     )
     .expect("Failed to write header");
 
     // Write data
-    for (w, l, run, stats) in results {
+    for (w, l, run, seed, stats) in results {
         writeln!(
             file,
-            "{},{},{},{:.6},{},{:.6}",
+            "{},{},{},{},{:.6},{},{},{},{:.6},{:.2},{},{},{}",
             w,
             l,
             run,
+            seed,
             stats.goodput / 1_000_000.0,
             stats.retransmissions,
-            stats.time
+            stats.timeout_retransmissions,
+            stats.nack_retransmissions,
+            stats.time,
+            stats.avg_window_size,
+            stats.min_window_size,
+            stats.reverse_ack_frames,
+            stats.undetected_errors
         )
         .expect("Failed to write row");
     }